@@ -1,5 +1,38 @@
-use crate::http::{Request, Response};
-use std::collections::HashMap;
+use crate::buffer;
+use crate::capture::Capture;
+use crate::clipboard;
+use crate::config::Config;
+use crate::dynamic;
+use crate::editor;
+use crate::graphql::{self, Schema};
+use crate::grpc;
+use crate::http::{self, BodyType, DEFAULT_RESPONSE_LIMIT, Header, Request, Response, send_request};
+use crate::auth::Auth;
+use crate::oauth::{self, Grant, OAuth2Config, Token};
+use crate::persistence::{
+    Environment, HISTORY_LIMIT, HistoryEntry, append_history, collection_scripts, list_requests,
+    load_environment, load_schema, remembered_header, save_environment, save_header_history,
+    save_schema, save_tokens,
+};
+use crate::query;
+use crate::runner;
+use crate::script::{self, Hook, Script};
+use crate::stream::{Stream, StreamMessage};
+use crate::util::format_size;
+use crate::websocket::{self, Connection, Outgoing, WsEvent};
+use prost_reflect::DescriptorPool;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+
+const MESSAGE_TIMEOUT: Duration = Duration::from_secs(5);
+const TIMING_HISTORY: usize = 5;
+// Stands in for the OAuth2 token in a preview when none is cached.
+const OAUTH_PENDING: &str = "<acquired when sent>";
+
+fn log_time() -> String {
+    chrono::Local::now().format("%H:%M:%S%.3f").to_string()
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Mode {
@@ -13,34 +46,111 @@ pub enum Mode {
 pub enum Panel {
     Sidebar,
     Url,
+    Params,
     Headers,
     Body,
+    Variables,
     Response,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseView {
+    Body,
+    Hex,
+    Timing,
+    Tests,
+    Environment,
+    Preview,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PendingCommand {
     Delete,
     Yank,
     Goto,
+    Register,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageKind {
+    Info,
+    Error,
+}
+
+#[derive(Debug, Clone)]
+pub struct StatusMessage {
+    pub text: String,
+    pub kind: MessageKind,
+    pub shown_at: Instant,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Completion {
+    pub candidates: Vec<String>,
+    pub index: usize,
+    pub start: usize,
+    pub prefix: String,
 }
 
 pub struct App {
     pub mode: Mode,
     pub active_panel: Panel,
     pub url_buffer: String,
+    pub params_buffer: String,
+    synced_url: String,
+    synced_params: String,
     pub body_buffer: String,
+    pub variables_buffer: String,
     pub command_buffer: String,
+    pub command_cursor: usize,
+    pub command_history: Vec<String>,
+    pub history_index: Option<usize>,
+    pub history_prefix: String,
+    pub completion: Option<Completion>,
+    pub insert_completion: Option<Completion>,
+    pub header_history: Vec<Header>,
+    // Recent sends, oldest first, for the Timing view's comparison.
+    pub history: Vec<HistoryEntry>,
     pub headers_buffer: String,
     pub pending_command: Option<PendingCommand>,
     pub current_request: Request,
     pub last_response: Option<Response>,
     pub response_buffer: String,
+    pub response_view: ResponseView,
+    pub test_report: String,
+    pub preview: String,
+    pub script_output: script::Output,
+    // The saved request being edited, whose folders' scripts apply.
+    pub request_name: Option<String>,
+    pub dynamic_values: RefCell<dynamic::Values>,
+    // The current data row of a collection run; looked up before the environment, never saved.
+    pub data_variables: BTreeMap<String, String>,
+    pub run: Option<runner::Background>,
+    pub editing_script: Option<Hook>,
+    pub stream: Option<(Stream, Instant)>,
+    pub websocket: Option<Connection>,
+    pub status_message: Option<StatusMessage>,
     pub cursors: HashMap<Panel, usize>,
     pub scroll_offsets: HashMap<Panel, u16>,
     pub should_quit: bool,
+    pub should_open_editor: bool,
     pub collections: Vec<String>,
+    pub environments: Vec<String>,
+    pub active_environment: Option<Environment>,
+    pub tokens: HashMap<String, Token>,
+    // A browser authorization in progress, with the cache key its token is stored under.
+    pub authorization: Option<(String, oauth::Authorization)>,
+    // Set by the TUI, where waiting for the browser must not block; headless callers wait.
+    pub interactive: bool,
+    // A background run's App: changes to the environment and tokens go back to the TUI, which
+    // saves them, instead of to disk.
+    pub detached: bool,
+    pub schemas: HashMap<String, Schema>,
+    pub descriptors: Option<(String, DescriptorPool)>,
+    pub grpc_methods: Vec<String>,
     pub yank_register: Option<String>,
+    pub selected_register: Option<char>,
+    pub config: Config,
     pub visual_anchor: Option<usize>,
     pub selected_collection_index: usize,
 }
@@ -50,8 +160,10 @@ impl App {
         let mut cursors = HashMap::new();
         cursors.insert(Panel::Sidebar, 0);
         cursors.insert(Panel::Url, 0);
+        cursors.insert(Panel::Params, 0);
         cursors.insert(Panel::Headers, 0);
         cursors.insert(Panel::Body, 0);
+        cursors.insert(Panel::Variables, 0);
         cursors.insert(Panel::Response, 0);
 
         let scroll_offsets = HashMap::new();
@@ -60,21 +172,57 @@ impl App {
             mode: Mode::Normal,
             active_panel: Panel::Url,
             url_buffer: String::from("https://api.example.com"),
+            params_buffer: String::new(),
+            synced_url: String::from("https://api.example.com"),
+            synced_params: String::new(),
             body_buffer: String::new(),
+            variables_buffer: String::new(),
             command_buffer: String::new(),
+            command_cursor: 0,
+            command_history: Vec::new(),
+            history_index: None,
+            history_prefix: String::new(),
+            completion: None,
+            insert_completion: None,
+            header_history: Vec::new(),
+            history: Vec::new(),
             headers_buffer: String::from("Content-Type: application/json"),
             current_request: Request::default(),
             last_response: None,
             response_buffer: String::from("No response yet. Press Enter to send request."),
+            response_view: ResponseView::Body,
+            test_report: String::new(),
+            preview: String::new(),
+            script_output: script::Output::default(),
+            request_name: None,
+            dynamic_values: RefCell::new(dynamic::Values::default()),
+            data_variables: BTreeMap::new(),
+            run: None,
+            authorization: None,
+            interactive: false,
+            detached: false,
+            editing_script: None,
+            stream: None,
+            websocket: None,
+            status_message: None,
             cursors,
             scroll_offsets,
             should_quit: false,
+            should_open_editor: false,
             collections: vec![
                 "My Requests".to_string(),
                 "API Tests".to_string(),
             ],
+            environments: Vec::new(),
+            active_environment: None,
+            tokens: HashMap::new(),
+            schemas: HashMap::new(),
+            descriptors: None,
+            grpc_methods: Vec::new(),
             pending_command: None,
             yank_register: None,
+            selected_register: None,
+            config: Config::default(),
             visual_anchor: None,
             selected_collection_index: 0,
         }
@@ -89,17 +237,186 @@ impl App {
 
     }
     pub fn update_response_buffer(&mut self) {
-        self.response_buffer = if let Some(ref response) = self.last_response {
-            format!("Status: {}\n\n{}", response.status, response.body)
+        self.response_buffer = match (&self.last_response, self.response_view) {
+            (_, ResponseView::Tests) if self.test_report.is_empty() => {
+                "No tests run yet. :test checks the request's assertions.".to_string()
+            }
+            (_, ResponseView::Tests) => self.test_report.clone(),
+            (_, ResponseView::Environment) => self.environment_text(),
+            (_, ResponseView::Preview) => self.preview.clone(),
+            (None, _) => "No response yet. Press Enter to send request.".to_string(),
+            (Some(response), ResponseView::Body) => {
+                let mut text = format!("Status: {}\n\n", response.status);
+                if let Some(path) = &response.body_file {
+                    text.push_str(&format!(
+                        "Body is {}, written to {} (showing the first {})\n\n",
+                        format_size(response.size()),
+                        path.display(),
+                        format_size(response.raw.len())
+                    ));
+                }
+                if response.binary {
+                    text.push_str(&format!(
+                        "Binary body ({}, {}); :saveresp <path> writes it to a file\n\n{}",
+                        response.header("content-type").unwrap_or("unknown type"),
+                        format_size(response.size()),
+                        response.hex_dump()
+                    ));
+                } else {
+                    text.push_str(&response.body);
+                }
+                text
+            }
+            (Some(response), ResponseView::Hex) => {
+                format!("Status: {}\n\n{}", response.status, response.hex_dump())
+            }
+            (Some(response), ResponseView::Timing) => self.timing_text(response),
+        };
+    }
+
+    // Switches to `view`, or back to the body when it is already shown.
+    pub fn toggle_response_view(&mut self, view: ResponseView) {
+        self.response_view = if self.response_view == view {
+            ResponseView::Body
         } else {
-            "No response yet. Press Enter to send request.".to_string()
+            view
         };
+        self.cursors.insert(Panel::Response, 0);
+        self.update_response_buffer();
+    }
+
+    // Removes the body file of the response being replaced.
+    pub fn set_last_response(&mut self, response: Option<Response>) {
+        if let Some(path) = self.last_response.as_ref().and_then(|r| r.body_file.as_ref()) {
+            let _ = std::fs::remove_file(path);
+        }
+        self.last_response = response;
+        if matches!(
+            self.response_view,
+            ResponseView::Tests | ResponseView::Environment | ResponseView::Preview
+        ) {
+            self.response_view = ResponseView::Body;
+        }
+        self.update_response_buffer();
+    }
+
+    pub fn save_response(&mut self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(response) = &self.last_response else {
+            return Err("no response to save".into());
+        };
+        let path = match path.strip_prefix("~/") {
+            Some(rest) => format!("{}/{}", std::env::var("HOME").unwrap_or_default(), rest),
+            None => path.to_string(),
+        };
+        response.save(&path)?;
+        let size = format_size(response.size());
+        self.info(format!("saved response to {} ({})", path, size));
+        Ok(())
+    }
+
+    fn environment_text(&self) -> String {
+        let mut text = match &self.active_environment {
+            Some(environment) => format!("Environment: {}\n", environment.name),
+            None => "No environment active (:env <name>)\n".to_string(),
+        };
+        if !self.environments.is_empty() {
+            text.push_str(&format!("Available: {}\n", self.environments.join(", ")));
+        }
+        if let Some(environment) = &self.active_environment {
+            text.push('\n');
+            for (name, value) in &environment.variables {
+                text.push_str(&format!("{} = {}\n", name, value));
+                if let Some(source) = environment.captured.get(name) {
+                    text.push_str(&format!("    captured: {}\n", source));
+                }
+            }
+        }
+        let values = self.dynamic_values.borrow();
+        if !values.expanded.is_empty() {
+            text.push_str(if values.pinned() {
+                "\nDynamic values for the next send:\n"
+            } else {
+                "\nDynamic values of the last request:\n"
+            });
+            for (expression, value) in &values.expanded {
+                text.push_str(&format!("{{{{{}}}}} = {}\n", expression, value));
+            }
+        }
+        text
+    }
+
+    pub fn record_history(&mut self, entry: HistoryEntry) {
+        let _ = append_history(&entry);
+        self.history.push(entry);
+        let excess = self.history.len().saturating_sub(HISTORY_LIMIT);
+        self.history.drain(..excess);
+    }
+
+    fn timing_text(&self, response: &Response) -> String {
+        let mut text = format!(
+            "Status: {}\n\n{}",
+            response.status,
+            response.timing.waterfall()
+        );
+
+        let method = self.current_request.method.to_string();
+        let url = self.substitute(&self.current_request.url);
+        let previous: Vec<&HistoryEntry> = self
+            .history
+            .iter()
+            .filter(|e| e.method == method && e.url == url)
+            .collect();
+        if previous.len() > 1 {
+            text.push_str("\n\nRecent:\n");
+            // The newest entry is the response shown above.
+            for entry in previous.iter().rev().skip(1).take(TIMING_HISTORY) {
+                let t = &entry.timing;
+                text.push_str(&format!(
+                    "{}  {}  total {:.1} ms  dns {:.1}  tcp {:.1}  tls {:.1}  ttfb {:.1}  download {:.1}\n",
+                    entry.sent_at,
+                    entry.status,
+                    t.total_ms,
+                    t.dns_ms,
+                    t.tcp_ms,
+                    t.tls_ms,
+                    t.ttfb_ms,
+                    t.download_ms
+                ));
+            }
+        }
+        text
+    }
+
+    pub fn set_message(&mut self, kind: MessageKind, text: impl Into<String>) {
+        self.status_message = Some(StatusMessage {
+            text: text.into(),
+            kind,
+            shown_at: Instant::now(),
+        });
+    }
+
+    pub fn info(&mut self, text: impl Into<String>) {
+        self.set_message(MessageKind::Info, text);
+    }
+
+    pub fn error(&mut self, text: impl Into<String>) {
+        self.set_message(MessageKind::Error, text);
+    }
+
+    pub fn current_message(&self) -> Option<&StatusMessage> {
+        self.status_message
+            .as_ref()
+            .filter(|m| m.shown_at.elapsed() < MESSAGE_TIMEOUT)
     }
 
     pub fn set_mode(&mut self, mode: Mode) {
         self.mode = mode;
+        self.insert_completion = None;
         if mode != Mode::Command {
             self.command_buffer.clear();
+            self.command_cursor = 0;
+            self.history_index = None;
+            self.completion = None;
         }
     }
 
@@ -137,14 +454,26 @@ impl App {
     }
 
     pub fn set_yank_register(&mut self, register: String) {
+        if matches!(self.selected_register.take(), Some('+' | '*')) {
+            clipboard::copy(self.config.clipboard, &register);
+        }
         self.yank_register = Some(register)
     }
 
+    pub fn register_contents(&mut self) -> Option<String> {
+        match self.selected_register.take() {
+            Some('+' | '*') => clipboard::paste(self.config.clipboard),
+            _ => self.yank_register.clone(),
+        }
+    }
+
     pub fn current_buffer(&self) -> &str {
         match self.active_panel {
             Panel::Url => &self.url_buffer,
+            Panel::Params => &self.params_buffer,
             Panel::Headers => &self.headers_buffer,
             Panel::Body => &self.body_buffer,
+            Panel::Variables => &self.variables_buffer,
             Panel::Response => &self.response_buffer,
             _ => "",
         }
@@ -153,22 +482,1089 @@ impl App {
     pub fn current_buffer_mut(&mut self) -> Option<&mut String> {
         match self.active_panel {
             Panel::Url => Some(&mut self.url_buffer),
+            Panel::Params => Some(&mut self.params_buffer),
             Panel::Headers => Some(&mut self.headers_buffer),
             Panel::Body => Some(&mut self.body_buffer),
+            Panel::Variables => Some(&mut self.variables_buffer),
             _ => None,
         }
     }
 
+    pub fn sync_query_params(&mut self) {
+        if self.params_buffer != self.synced_params {
+            let params = query::parse_params(&self.params_buffer);
+            self.url_buffer = query::url_with_params(&self.url_buffer, &params);
+        } else if self.url_buffer != self.synced_url {
+            let previous = query::parse_params(&self.params_buffer);
+            let params = query::merge_disabled(query::params_from_url(&self.url_buffer), &previous);
+            self.params_buffer = query::format_params(&params);
+            let cursor = buffer::clamp(&self.params_buffer, self.cursors[&Panel::Params]);
+            self.cursors.insert(Panel::Params, cursor);
+        } else {
+            return;
+        }
+        self.synced_url = self.url_buffer.clone();
+        self.synced_params = self.params_buffer.clone();
+    }
+
+    pub fn disabled_params(&self) -> Vec<query::QueryParam> {
+        query::parse_params(&self.params_buffer)
+            .into_iter()
+            .filter(|p| !p.enabled)
+            .collect()
+    }
+
     pub fn parsed_headers(&self) -> Vec<(String, String)> {
-        self.headers_buffer
-            .lines()
-            .filter_map(|line| {
-                let (key, value) = line.split_once(':')?;
-                Some((key.trim().to_string(), value.trim().to_string()))
-            })
+        http::parse_headers(&self.headers_buffer)
+            .into_iter()
+            .filter(|h| h.enabled)
+            .map(|h| (h.name, h.value))
             .collect()
     }
 
+    fn variable(&self, name: &str) -> Option<String> {
+        if let Some(value) = self.data_variables.get(name) {
+            return Some(value.clone());
+        }
+        self.active_environment.as_ref()?.variables.get(name).cloned()
+    }
+
+    pub fn substitute(&self, text: &str) -> String {
+        dynamic::substitute(text, &mut |name| {
+            if name.starts_with('$') {
+                dynamic::evaluate(name)
+            } else {
+                self.variable(name)
+            }
+        })
+    }
+
+    // Like substitute, but records the dynamic values it expands (or reuses pinned ones).
+    fn resolve_text(&self, text: &str) -> String {
+        let mut values = self.dynamic_values.borrow_mut();
+        dynamic::substitute(text, &mut |name| {
+            if name.starts_with('$') {
+                values.next(name)
+            } else {
+                self.variable(name)
+            }
+        })
+    }
+
+    pub fn build_request(&self) -> Request {
+        let mut req = Request::new(self.current_request.method, self.url_buffer.clone());
+        for header in http::parse_headers(&self.headers_buffer) {
+            req = req.with_header(header);
+        }
+        req.disabled_params = self.disabled_params();
+        req.body_type = self.current_request.body_type;
+        req.auth = self.current_request.auth.clone();
+        req.proto = self.current_request.proto.clone();
+        req.assertions = self.current_request.assertions.clone();
+        req.captures = self.current_request.captures.clone();
+        req.pre_script = self.current_request.pre_script.clone();
+        req.post_script = self.current_request.post_script.clone();
+        if req.body_type == BodyType::GraphQL {
+            req.variables = self.variables_buffer.clone();
+        }
+        req.with_body(self.body_buffer.clone())
+    }
+
+    pub fn apply_request(&mut self, request: Request) {
+        self.url_buffer = request.url.clone();
+        self.headers_buffer = http::format_headers(&request.headers);
+        self.body_buffer = request.body.clone();
+        self.variables_buffer = request.variables.clone();
+        let mut params = query::params_from_url(&request.url);
+        params.extend(request.disabled_params.iter().cloned());
+        self.params_buffer = query::format_params(&params);
+        self.synced_url = self.url_buffer.clone();
+        self.synced_params = self.params_buffer.clone();
+        if request.body_type != BodyType::GraphQL && self.active_panel == Panel::Variables {
+            self.active_panel = Panel::Body;
+        }
+        let proto = request.proto.clone();
+        self.current_request = request;
+        for panel in [Panel::Url, Panel::Params, Panel::Headers, Panel::Body, Panel::Variables] {
+            self.cursors.insert(panel, 0);
+        }
+        if !proto.is_empty()
+            && let Err(err) = self.load_proto(&proto)
+        {
+            self.error(format!("E: {}", err));
+        }
+    }
+
+    // Loads the services the Sidebar offers for grpc:// requests.
+    pub fn load_proto(&mut self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let pool = grpc::load_descriptors(path)?;
+        self.grpc_methods = grpc::methods(&pool);
+        self.descriptors = Some((path.to_string(), pool));
+        self.current_request.proto = path.to_string();
+        self.selected_collection_index = self
+            .selected_collection_index
+            .min((self.collections.len() + self.grpc_methods.len()).saturating_sub(1));
+        Ok(())
+    }
+
+    // Points the URL at `method` on the current host and fills an empty Body with its input message.
+    pub fn select_grpc_method(&mut self, method: &str) {
+        let base = if grpc::is_grpc_url(&self.url_buffer) {
+            let url = self.url_buffer.trim();
+            let after_scheme = url.find("://").map_or(0, |i| i + 3);
+            let end = url[after_scheme..].find('/').map_or(url.len(), |i| after_scheme + i);
+            url[..end].to_string()
+        } else {
+            "grpc://localhost:50051".to_string()
+        };
+        self.url_buffer = format!("{}/{}", base, method);
+        self.cursors.insert(Panel::Url, self.url_buffer.len());
+        if self.body_buffer.trim().is_empty()
+            && let Some((_, pool)) = &self.descriptors
+            && let Some(template) = grpc::message_template(pool, method)
+        {
+            self.body_buffer = template;
+            self.cursors.insert(Panel::Body, 0);
+        }
+        self.current_request.body_type = BodyType::Json;
+        self.set_panel(Panel::Body);
+    }
+
+    // Substitutes variables and resolves OAuth2 to a token; errors are reported on the status line.
+    fn resolve_current_request(&mut self) -> Option<Request> {
+        match self.resolve_request() {
+            Ok(resolved) => Some(resolved),
+            Err(err) => {
+                self.error(format!("E: {}", err));
+                None
+            }
+        }
+    }
+
+    pub fn resolve_request(&mut self) -> Result<Request, Box<dyn std::error::Error>> {
+        self.resolve(false)
+    }
+
+    // A dry run shows what would be sent without side effects: variables scripts set only apply
+    // to this request, and OAuth2 uses a cached token but never fetches one.
+    fn resolve(&mut self, dry_run: bool) -> Result<Request, Box<dyn std::error::Error>> {
+        self.current_request = self.build_request();
+
+        let mut resolved = self.current_request.clone();
+        self.dynamic_values.borrow_mut().start();
+        self.substitute_request(&mut resolved);
+        resolved.auth = resolved.auth.map_values(|v| self.resolve_text(v));
+
+        // The script sees the substituted request; variables it sets are substituted afterwards.
+        self.script_output = script::Output::default();
+        let scripts = self.scripts(Hook::Pre)?;
+        if !scripts.is_empty() {
+            let mut vars = self.variables();
+            self.script_output = script::pre_request(&scripts, &mut resolved, &mut vars)?;
+            if dry_run {
+                // Substituted like a data row, which is looked up first and never saved.
+                let data = std::mem::replace(&mut self.data_variables, vars);
+                self.substitute_request(&mut resolved);
+                self.data_variables = data;
+            } else {
+                self.set_variables(vars);
+                self.substitute_request(&mut resolved);
+            }
+        }
+        if let Auth::OAuth2(config) = resolved.auth.clone() {
+            let token = if dry_run {
+                let cached = self.tokens.get(&self.oauth_cache_key(&config));
+                match cached.filter(|token| !token.needs_refresh()) {
+                    Some(token) => token.access_token.clone(),
+                    None => OAUTH_PENDING.to_string(),
+                }
+            } else {
+                self.oauth_token(&config, false).map_err(|err| format!("oauth: {}", err))?
+            };
+            resolved.auth = Auth::Bearer { token };
+        }
+        self.dynamic_values.borrow_mut().unpin();
+        Ok(resolved)
+    }
+
+    fn substitute_request(&self, request: &mut Request) {
+        request.url = self.resolve_text(&request.url);
+        for header in &mut request.headers {
+            header.name = self.resolve_text(&header.name);
+            header.value = self.resolve_text(&header.value);
+        }
+        request.body = self.resolve_text(&request.body);
+        request.variables = self.resolve_text(&request.variables);
+    }
+
+    // Shows the request as it would be sent, pinning its dynamic values so the next send matches.
+    pub fn preview_request(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let resolved = self.resolve(true)?;
+        self.dynamic_values.borrow_mut().pin();
+
+        let mut warnings = http::header_warnings(&self.headers_buffer);
+        let url = resolved.url.trim();
+        let plain_http = !websocket::is_websocket_url(url) && !grpc::is_grpc_url(url);
+        match reqwest::Url::parse(url) {
+            Err(err) => warnings.push(format!("malformed URL: {}", err)),
+            Ok(parsed) if plain_http && !matches!(parsed.scheme(), "http" | "https") => {
+                warnings.push(format!("unsupported URL scheme: {}", parsed.scheme()));
+            }
+            Ok(_) if url.contains(' ') => warnings.push("spaces in the URL are sent as %20".to_string()),
+            Ok(_) => {}
+        }
+        let texts = std::iter::once(&resolved.url)
+            .chain(resolved.headers.iter().filter(|h| h.enabled).map(|h| &h.value))
+            .chain(std::iter::once(&resolved.body));
+        for text in texts {
+            let mut rest = text.as_str();
+            while let Some(start) = rest.find("{{") {
+                let end = rest[start..].find("}}").map_or(rest.len(), |i| start + i + 2);
+                warnings.push(format!("unresolved placeholder: {}", &rest[start..end]));
+                rest = &rest[end..];
+            }
+        }
+        match &resolved.auth {
+            Auth::Digest { .. } => {
+                warnings.push("Digest auth adds Authorization after the server's 401 challenge".to_string());
+            }
+            Auth::Bearer { token } if token == OAUTH_PENDING => {
+                warnings.push("no valid OAuth2 token is cached; one is acquired when sent".to_string());
+            }
+            _ => {}
+        }
+
+        let request = if plain_http {
+            http::preview(&resolved).unwrap_or_else(|err| {
+                warnings.push(format!("cannot build the request: {}", http::describe_error(err.as_ref())));
+                String::new()
+            })
+        } else {
+            warnings.push("WebSocket and gRPC requests are shown as resolved, not as sent".to_string());
+            let mut text = format!("{} {}\n", resolved.method, resolved.url);
+            for header in resolved.headers.iter().filter(|h| h.enabled) {
+                text.push_str(&format!("{}: {}\n", header.name, header.value));
+            }
+            format!("{}\n{}", text, resolved.body)
+        };
+
+        self.preview = String::new();
+        for warning in &warnings {
+            self.preview.push_str(&format!("! {}\n", warning));
+        }
+        if !warnings.is_empty() {
+            self.preview.push('\n');
+        }
+        self.preview.push_str(&request);
+        self.response_view = ResponseView::Preview;
+        self.cursors.insert(Panel::Response, 0);
+        self.update_response_buffer();
+        self.set_panel(Panel::Response);
+        match warnings.len() {
+            0 if self.dynamic_values.borrow().pinned() => {
+                self.info("preview: the next send reuses its dynamic values")
+            }
+            0 => self.info("preview"),
+            n => self.error(format!("E: preview: {} warnings", n)),
+        }
+        Ok(())
+    }
+
+    // Expands the request's dynamic variables now and shows them; the next send reuses them.
+    pub fn pin_dynamic_values(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.resolve(true)?;
+        self.dynamic_values.borrow_mut().pin();
+        let count = self.dynamic_values.borrow().expanded.len();
+        self.show_environment();
+        self.info(format!("{} dynamic values resolved for the next send", count));
+        Ok(())
+    }
+
+    // Folder scripts of the loaded request come before its own script.
+    fn scripts(&self, hook: Hook) -> Result<Vec<Script>, Box<dyn std::error::Error>> {
+        let mut scripts = match &self.request_name {
+            Some(name) => collection_scripts(name, hook)?,
+            None => Vec::new(),
+        };
+        let source = match hook {
+            Hook::Pre => &self.current_request.pre_script,
+            Hook::Post => &self.current_request.post_script,
+        };
+        if !source.trim().is_empty() {
+            scripts.push((format!("{}-request script", hook.name()), source.clone()));
+        }
+        Ok(scripts)
+    }
+
+    // Runs the post-response scripts; failures are reported as failed assertions.
+    pub fn run_post_scripts(&mut self, request: &Request, response: &Response) {
+        let scripts = match self.scripts(Hook::Post) {
+            Ok(scripts) => scripts,
+            Err(err) => {
+                let failure = Some(err.to_string());
+                self.script_output.assertions.push(("post-response scripts".to_string(), failure));
+                return;
+            }
+        };
+        if scripts.is_empty() {
+            return;
+        }
+        let mut vars = self.variables();
+        match script::post_response(&scripts, request, response, &mut vars) {
+            Ok(output) => {
+                self.script_output.logs.extend(output.logs);
+                self.script_output.assertions.extend(output.assertions);
+            }
+            Err(err) => {
+                self.script_output.assertions.push(("post-response script".to_string(), Some(err)));
+            }
+        }
+        self.set_variables(vars);
+    }
+
+    // Puts script logs and assertions in the Tests view without switching to it.
+    fn report_scripts(&mut self, request: &Request, response: &Response) {
+        if self.script_output.is_empty() {
+            return;
+        }
+        let mut report = runner::Report::new("current", request);
+        report.status = Some(response.status);
+        report.duration_ms = response.duration_ms;
+        report.add_script(&self.script_output);
+        self.test_report = runner::report_text(&[report]);
+        match self.script_output.failures() {
+            0 => {
+                let logs = self.script_output.logs.len();
+                self.info(format!("script: {} log lines (T shows them)", logs));
+            }
+            failed => self.error(format!("E: script: {} assertions failed (T shows them)", failed)),
+        }
+    }
+
+    pub fn send_current_request(&mut self) {
+        let Some(resolved) = self.resolve_current_request() else {
+            return;
+        };
+        self.remember_headers();
+        if websocket::is_websocket_url(&resolved.url) {
+            self.send_websocket(resolved);
+            return;
+        }
+        // Dropping the connection closes it.
+        self.websocket = None;
+        if grpc::is_grpc_url(&resolved.url) {
+            self.send_grpc(resolved);
+            return;
+        }
+        let accepts_events = resolved.headers.iter().any(|h| {
+            h.enabled
+                && h.name.eq_ignore_ascii_case("accept")
+                && h.value.contains("text/event-stream")
+        });
+        if accepts_events {
+            self.start_stream(Stream::start(resolved));
+            return;
+        }
+
+        let limit = self.config.response_limit.unwrap_or(DEFAULT_RESPONSE_LIMIT);
+        let response = match send_request(&resolved, limit) {
+            Ok(response) => {
+                self.record_history(HistoryEntry::new(
+                    resolved.method.to_string(),
+                    resolved.url.clone(),
+                    response.status,
+                    response.timing.clone(),
+                ));
+                let captured = self.capture(&resolved, &response);
+                self.report_captures(&captured);
+                self.run_post_scripts(&resolved, &response);
+                self.report_scripts(&resolved, &response);
+                response
+            }
+            Err(err) => {
+                self.error(format!("E: {}", err));
+                Response::default()
+            }
+        };
+        self.set_last_response(Some(response));
+        self.set_panel(Panel::Response);
+    }
+
+    // Sends the request and shows which of its assertions passed in the Tests view.
+    pub fn test_current_request(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let resolved = self.resolve_request()?;
+        if resolved.assertions.is_empty() && self.scripts(Hook::Post)?.is_empty() {
+            return Err("no assertions (add one with :assert <assertion>)".into());
+        }
+        self.remember_headers();
+        self.stop_stream();
+        self.websocket = None;
+        let response = runner::send(self, &resolved)?;
+        let mut report = runner::Report::new("current", &resolved);
+        report.check(&resolved, &response);
+        let captured = self.capture(&resolved, &response);
+        report.add_captures(&captured);
+        self.run_post_scripts(&resolved, &response);
+        report.add_script(&self.script_output);
+        self.set_last_response(Some(response));
+        self.show_test_reports(&[report]);
+        Ok(())
+    }
+
+    // Runs a saved request or folder in the background without touching the request being
+    // edited; the Tests view follows its progress.
+    pub fn run_collection(
+        &mut self,
+        name: &str,
+        options: runner::Options,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if self.run.is_some() {
+            return Err("a run is already in progress (Esc stops it)".into());
+        }
+        if let Some(path) = &options.data {
+            runner::load_data(path)?;
+        }
+        list_requests(name.strip_suffix(".json").unwrap_or(name))?;
+        self.run = Some(runner::Background::start(self, name, options));
+        self.test_report = format!("Running {}...\n", name);
+        self.show_tests();
+        self.info(format!("running {}", name));
+        Ok(())
+    }
+
+    pub fn stop_run(&mut self) -> bool {
+        let Some(run) = &self.run else {
+            return false;
+        };
+        run.stop();
+        self.info("stopping run");
+        true
+    }
+
+    pub fn poll_run(&mut self) {
+        let Some(run) = &mut self.run else {
+            return;
+        };
+        let before: usize = run.iterations.iter().map(|i| i.reports.len()).sum();
+        let finished = run.poll();
+        let after: usize = run.iterations.iter().map(|i| i.reports.len()).sum();
+        if after != before || finished.is_some() {
+            self.test_report = runner::summary_text(&run.iterations);
+            if self.response_view == ResponseView::Tests {
+                self.update_response_buffer();
+            }
+        }
+        let Some(runner::Event::Finished { result, changes }) = finished else {
+            return;
+        };
+        let run = self.run.take().expect("checked above");
+        let applied = self.apply_run_changes(changes);
+        if let Err(err) = result {
+            self.error(format!("E: {}", err));
+            return;
+        }
+        let reports: Vec<&runner::Report> = run.iterations.iter().flat_map(|i| &i.reports).collect();
+        let failed = reports.iter().filter(|r| r.failed()).count();
+        let note = if applied { "" } else { " (environment switched; its variables were not updated)" };
+        if failed > 0 {
+            self.error(format!("E: {}: {} of {} failed{}", run.name, failed, reports.len(), note));
+        } else {
+            self.info(format!("{}: {} passed{}", run.name, reports.len(), note));
+        }
+    }
+
+    // Merges what a background run changed into the current environment, unless the user has
+    // switched to another one since; then the run's variables stay its own. Returns whether
+    // there was nothing to skip.
+    fn apply_run_changes(&mut self, changes: runner::Changes) -> bool {
+        if !changes.tokens.is_empty() {
+            self.tokens.extend(changes.tokens.clone());
+            if let Err(err) = self.save_tokens() {
+                self.error(format!("E: cannot save tokens: {}", err));
+            }
+        }
+        if !changes.has_variables() {
+            return true;
+        }
+        let current = self.active_environment.as_ref().map_or("default", |e| e.name.as_str());
+        if current != changes.environment {
+            return false;
+        }
+        let environment = self.environment_mut();
+        for (values, updates) in [
+            (&mut environment.variables, changes.variables),
+            (&mut environment.captured, changes.captured),
+        ] {
+            for (name, value) in updates {
+                match value {
+                    Some(value) => values.insert(name, value),
+                    None => values.remove(&name),
+                };
+            }
+        }
+        self.save_environment();
+        true
+    }
+
+    fn show_tests(&mut self) {
+        self.response_view = ResponseView::Tests;
+        self.cursors.insert(Panel::Response, 0);
+        self.update_response_buffer();
+        self.set_panel(Panel::Response);
+    }
+
+    fn show_test_reports(&mut self, reports: &[runner::Report]) {
+        let failed = reports.iter().filter(|r| r.failed()).count();
+        self.test_report = runner::report_text(reports);
+        self.show_tests();
+        if failed > 0 {
+            self.error(format!("E: {} of {} failed", failed, reports.len()));
+        } else {
+            self.info(format!("{} passed", reports.len()));
+        }
+    }
+
+    // Stores the values a successful response yields for the request's captures in the active
+    // environment, starting a `default` one when none is active, and saves it. Returns each
+    // capture with the reason it failed, if it did.
+    pub fn capture(&mut self, request: &Request, response: &Response) -> Vec<(Capture, Option<String>)> {
+        if request.captures.is_empty() || !(100..400).contains(&response.status) {
+            return Vec::new();
+        }
+        let environment = self.environment_mut();
+        let origin = format!("{} {}", request.method, request.url);
+        let mut results = Vec::new();
+        for capture in &request.captures {
+            match capture.extract(response) {
+                Ok(value) => {
+                    environment.variables.insert(capture.variable.clone(), value);
+                    environment
+                        .captured
+                        .insert(capture.variable.clone(), format!("{} from {}", capture.source, origin));
+                    results.push((capture.clone(), None));
+                }
+                Err(reason) => results.push((capture.clone(), Some(reason))),
+            }
+        }
+        self.save_environment();
+        results
+    }
+
+    // The active environment, starting a `default` one when none is active.
+    fn environment_mut(&mut self) -> &mut Environment {
+        self.active_environment.get_or_insert_with(|| {
+            load_environment("default").unwrap_or_else(|_| Environment {
+                name: "default".to_string(),
+                ..Environment::default()
+            })
+        })
+    }
+
+    fn save_environment(&mut self) {
+        if self.detached {
+            return;
+        }
+        let environment = self.environment_mut();
+        let name = environment.name.clone();
+        if let Err(err) = save_environment(environment) {
+            self.error(format!("E: cannot save environment {}: {}", name, err));
+        }
+        if !self.environments.contains(&name) {
+            self.environments.push(name);
+            self.environments.sort();
+        }
+        if self.response_view == ResponseView::Environment {
+            self.update_response_buffer();
+        }
+    }
+
+    fn environment_variables(&self) -> BTreeMap<String, String> {
+        self.active_environment
+            .as_ref()
+            .map(|environment| environment.variables.clone())
+            .unwrap_or_default()
+    }
+
+    // What scripts see: the environment, overridden by the runner's data row.
+    fn variables(&self) -> BTreeMap<String, String> {
+        let mut variables = self.environment_variables();
+        variables.extend(self.data_variables.clone());
+        variables
+    }
+
+    // Saves variables a script changed into the active environment. Data row values it left
+    // alone are not saved.
+    fn set_variables(&mut self, mut variables: BTreeMap<String, String>) {
+        let environment = self.environment_variables();
+        for (name, value) in &self.data_variables {
+            if variables.get(name) == Some(value) {
+                match environment.get(name) {
+                    Some(original) => variables.insert(name.clone(), original.clone()),
+                    None => variables.remove(name),
+                };
+            }
+        }
+        if variables == environment {
+            return;
+        }
+        let environment = self.environment_mut();
+        environment.captured.retain(|name, _| variables.contains_key(name));
+        environment.variables = variables;
+        self.save_environment();
+    }
+
+    fn report_captures(&mut self, results: &[(Capture, Option<String>)]) {
+        if let Some((capture, Some(reason))) = results.iter().find(|(_, failure)| failure.is_some()) {
+            self.error(format!("E: capture {}: {}", capture.variable, reason));
+        } else if !results.is_empty() {
+            let names: Vec<&str> = results.iter().map(|(c, _)| c.variable.as_str()).collect();
+            self.info(format!("captured {}", names.join(", ")));
+        }
+    }
+
+    pub fn show_environment(&mut self) {
+        self.response_view = ResponseView::Environment;
+        self.cursors.insert(Panel::Response, 0);
+        self.update_response_buffer();
+        self.set_panel(Panel::Response);
+    }
+
+    pub fn stream_current_request(&mut self) {
+        if let Some(resolved) = self.resolve_current_request() {
+            self.remember_headers();
+            self.start_stream(Stream::start(resolved));
+        }
+    }
+
+    fn start_stream(&mut self, stream: Stream) {
+        self.stop_stream();
+        self.websocket = None;
+        self.set_last_response(None);
+        self.response_buffer = "Waiting for response...".to_string();
+        self.cursors.insert(Panel::Response, 0);
+        self.stream = Some((stream, Instant::now()));
+        self.set_panel(Panel::Response);
+    }
+
+    // Responses arrive as a stream so server-streaming methods show each message as it comes.
+    fn send_grpc(&mut self, request: Request) {
+        if request.proto.is_empty() {
+            self.error("E: no descriptors loaded (use :proto <path>)");
+            return;
+        }
+        if self.descriptors.as_ref().is_none_or(|(path, _)| *path != request.proto)
+            && let Err(err) = self.load_proto(&request.proto)
+        {
+            self.error(format!("E: {}", err));
+            return;
+        }
+        let Some((_, pool)) = &self.descriptors else {
+            return;
+        };
+        match grpc::call(request, pool) {
+            Ok(stream) => self.start_stream(stream),
+            Err(err) => self.error(format!("E: {}", err)),
+        }
+    }
+
+    pub fn stop_stream(&mut self) -> bool {
+        let Some((stream, _)) = self.stream.take() else {
+            return false;
+        };
+        stream.stop();
+        self.info("stream stopped");
+        true
+    }
+
+    // Appends whatever the stream delivered since the last call, following the
+    // output while the cursor is on the last line.
+    pub fn poll_stream(&mut self) {
+        let Some((stream, started)) = &self.stream else {
+            return;
+        };
+        let started = *started;
+        let messages = stream.messages();
+        if messages.is_empty() {
+            return;
+        }
+
+        let follow = self.response_following();
+        let limit = self.config.response_limit.unwrap_or(DEFAULT_RESPONSE_LIMIT);
+
+        for message in messages {
+            match message {
+                StreamMessage::Started(response) => self.last_response = Some(response),
+                StreamMessage::Text(text) => self.append_to_response(&text, limit),
+                StreamMessage::Event(event) => {
+                    let mut header = format!(
+                        "[{}] {}",
+                        log_time(),
+                        event.event.as_deref().unwrap_or("message")
+                    );
+                    if let Some(id) = &event.id {
+                        header.push_str(&format!(" id={}", id));
+                    }
+                    self.append_to_response(&format!("{}\n{}\n\n", header, event.data), limit);
+                }
+                StreamMessage::Failed(err) => {
+                    self.stream = None;
+                    self.error(format!("E: {}", err));
+                }
+                StreamMessage::Finished => {
+                    self.stream = None;
+                    self.info("stream finished");
+                }
+            }
+        }
+        if let Some(response) = &mut self.last_response {
+            response.duration_ms = started.elapsed().as_millis();
+        }
+        self.refresh_response(follow);
+    }
+
+    // Long-running streams keep only the most recent `limit` bytes.
+    fn append_to_response(&mut self, text: &str, limit: usize) {
+        let Some(response) = &mut self.last_response else {
+            return;
+        };
+        response.body.push_str(text);
+        response.timing.response_body_bytes += text.len();
+        if response.body.len() > limit {
+            let mut cut = response.body.len() - limit;
+            while !response.body.is_char_boundary(cut) {
+                cut += 1;
+            }
+            if let Some(i) = response.body[cut..].find('\n') {
+                cut += i + 1;
+            }
+            response.body.drain(..cut);
+        }
+    }
+
+    fn response_following(&self) -> bool {
+        let cursor = *self.cursors.get(&Panel::Response).unwrap_or(&0);
+        cursor >= self.response_buffer.rfind('\n').map_or(0, |i| i + 1)
+    }
+
+    fn refresh_response(&mut self, follow: bool) {
+        self.update_response_buffer();
+        if follow {
+            let end = self.response_buffer.rfind('\n').map_or(0, |i| i + 1);
+            self.cursors.insert(Panel::Response, end);
+        }
+    }
+
+    fn send_websocket(&mut self, request: Request) {
+        if let Some(connection) = &self.websocket {
+            let message = if request.body_type == BodyType::Binary {
+                let path = request.body.trim();
+                let path = path.strip_prefix('@').unwrap_or(path);
+                match std::fs::read(path) {
+                    Ok(bytes) => Outgoing::Binary(bytes),
+                    Err(err) => {
+                        self.error(format!("E: cannot read {}: {}", path, err));
+                        return;
+                    }
+                }
+            } else {
+                Outgoing::Text(request.body)
+            };
+            if !connection.send(message) {
+                self.websocket = None;
+                self.error("E: websocket is not connected");
+            }
+            return;
+        }
+
+        match Connection::open(&request) {
+            Ok(connection) => {
+                self.stop_stream();
+                self.set_last_response(None);
+                self.response_buffer = format!("Connecting to {}...", request.url);
+                self.cursors.insert(Panel::Response, 0);
+                self.websocket = Some(connection);
+                self.set_panel(Panel::Response);
+            }
+            Err(err) => self.error(format!("E: {}", err)),
+        }
+    }
+
+    pub fn websocket_send(&mut self, message: Outgoing) -> Result<(), Box<dyn std::error::Error>> {
+        match &self.websocket {
+            Some(connection) if connection.send(message) => Ok(()),
+            _ => Err("websocket is not connected".into()),
+        }
+    }
+
+    // Logs frames exchanged since the last call.
+    pub fn poll_websocket(&mut self) {
+        let Some(connection) = &self.websocket else {
+            return;
+        };
+        let events = connection.events();
+        if events.is_empty() {
+            return;
+        }
+
+        let follow = self.response_following();
+        let limit = self.config.response_limit.unwrap_or(DEFAULT_RESPONSE_LIMIT);
+        for event in events {
+            let line = match event {
+                WsEvent::Connected(response) => {
+                    let line = format!("* connected ({})", response.status_text);
+                    self.last_response = Some(response);
+                    line
+                }
+                WsEvent::Sent(message) => format!("> {}", websocket::describe(&message)),
+                WsEvent::Received(message) => format!("< {}", websocket::describe(&message)),
+                WsEvent::Closed => {
+                    self.websocket = None;
+                    self.info("websocket closed");
+                    "* closed".to_string()
+                }
+                WsEvent::Failed(err) => {
+                    self.websocket = None;
+                    self.error(format!("E: {}", err));
+                    format!("* failed: {}", err)
+                }
+            };
+            self.append_to_response(&format!("[{}] {}\n", log_time(), line), limit);
+        }
+        self.refresh_response(follow);
+    }
+
+    fn oauth_cache_key(&self, config: &OAuth2Config) -> String {
+        let environment = self
+            .active_environment
+            .as_ref()
+            .map_or("default", |env| env.name.as_str());
+        config.cache_key(environment)
+    }
+
+    pub fn oauth_token(
+        &mut self,
+        config: &OAuth2Config,
+        force: bool,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let key = self.oauth_cache_key(config);
+
+        if !force && let Some(token) = self.tokens.get(&key) {
+            if !token.needs_refresh() {
+                return Ok(token.access_token.clone());
+            }
+            if let Some(refresh_token) = token.refresh_token.clone()
+                && let Ok(token) = oauth::refresh(config, &refresh_token)
+            {
+                let access_token = token.access_token.clone();
+                self.tokens.insert(key, token);
+                self.save_tokens()?;
+                return Ok(access_token);
+            }
+        }
+
+        let backend = self.config.clipboard;
+        let open_url = |url: &str| {
+            // The URL is also copied so it can be opened by hand over SSH.
+            clipboard::copy(backend, url);
+            oauth::open_in_browser(url);
+            log!("oauth: authorize at {}", url);
+        };
+        if self.interactive && config.grant == Grant::AuthorizationCode {
+            if self.authorization.as_ref().is_none_or(|(pending, _)| *pending != key) {
+                self.cancel_authorization();
+                self.authorization = Some((key, oauth::Authorization::start(config, open_url)?));
+            }
+            return Err("authorize in the browser, then retry (Esc cancels)".into());
+        }
+        let token = oauth::acquire(config, open_url)?;
+        let access_token = token.access_token.clone();
+        self.tokens.insert(key, token);
+        self.save_tokens()?;
+        Ok(access_token)
+    }
+
+    pub fn poll_authorization(&mut self) {
+        let Some(result) = self.authorization.as_ref().and_then(|(_, a)| a.poll()) else {
+            return;
+        };
+        let Some((key, _)) = self.authorization.take() else {
+            return;
+        };
+        match result {
+            Ok(token) => {
+                self.tokens.insert(key, token);
+                match self.save_tokens() {
+                    Ok(()) => self.info("oauth: token acquired"),
+                    Err(err) => self.error(format!("E: oauth: cannot save token: {}", err)),
+                }
+            }
+            Err(err) => self.error(format!("E: oauth: {}", err)),
+        }
+    }
+
+    pub fn cancel_authorization(&mut self) -> bool {
+        let Some((_, authorization)) = self.authorization.take() else {
+            return false;
+        };
+        authorization.cancel();
+        self.info("oauth: authorization cancelled");
+        true
+    }
+
+    pub fn clear_oauth_token(&mut self, config: &OAuth2Config) -> Result<(), Box<dyn std::error::Error>> {
+        let key = self.oauth_cache_key(&config.map_values(|v| self.substitute(v)));
+        self.tokens.remove(&key);
+        self.save_tokens()
+    }
+
+    fn save_tokens(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.detached {
+            return Ok(());
+        }
+        save_tokens(&self.tokens)
+    }
+
+    pub fn oauth_status(&self) -> Option<String> {
+        let Auth::OAuth2(config) = &self.current_request.auth else {
+            return None;
+        };
+        let key = self.oauth_cache_key(&config.map_values(|v| self.substitute(v)));
+        let status = match self.tokens.get(&key) {
+            None => "no token".to_string(),
+            Some(token) => match token.seconds_left() {
+                None => "token valid".to_string(),
+                Some(left) if left <= 0 => "token expired".to_string(),
+                Some(left) if left < 60 => format!("token {}s", left),
+                Some(left) => format!("token {}m", left / 60),
+            },
+        };
+        Some(format!("oauth: {}", status))
+    }
+
+    // Adds the headers of a request sent from the TUI to the completion history.
+    fn remember_headers(&mut self) {
+        let mut changed = false;
+        for header in self.current_request.headers.iter().filter(|h| h.enabled) {
+            let header = remembered_header(header);
+            let known = self
+                .header_history
+                .iter()
+                .any(|h| h.name.eq_ignore_ascii_case(&header.name) && h.value == header.value);
+            if !known && !header.value.contains("{{") {
+                self.header_history.push(header);
+                changed = true;
+            }
+        }
+        if changed {
+            let _ = save_header_history(&self.header_history);
+        }
+    }
+
+    fn schema_key(url: &str) -> String {
+        url.trim().split(['?', '#']).next().unwrap_or_default().to_string()
+    }
+
+    // Runs the introspection query against the current endpoint and caches the result.
+    pub fn fetch_schema(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(mut request) = self.resolve_current_request() else {
+            return Ok(());
+        };
+        request.method = http::Method::POST;
+        request.body_type = BodyType::GraphQL;
+        request.body = graphql::INTROSPECTION_QUERY.to_string();
+        request.variables.clear();
+
+        let limit = self.config.response_limit.unwrap_or(DEFAULT_RESPONSE_LIMIT);
+        let response = send_request(&request, limit)?;
+        if response.status >= 400 {
+            return Err(format!("introspection failed: {}", response.status_text).into());
+        }
+        let json: serde_json::Value = serde_json::from_str(&response.body)?;
+        let schema = Schema::from_introspection(&json)?;
+
+        let key = Self::schema_key(&request.url);
+        save_schema(&key, &schema)?;
+        self.info(format!("GraphQL schema: {} types", schema.types.len()));
+        self.schemas.insert(key, schema);
+        Ok(())
+    }
+
+    fn current_schema_key(&self) -> String {
+        Self::schema_key(&self.substitute(&self.url_buffer))
+    }
+
+    // Loads the schema cached on disk for the endpoint in the URL bar.
+    pub fn load_schema(&mut self) {
+        let key = self.current_schema_key();
+        if !self.schemas.contains_key(&key)
+            && let Ok(Some(schema)) = load_schema(&key)
+        {
+            self.schemas.insert(key, schema);
+        }
+    }
+
+    pub fn schema(&self) -> Option<&Schema> {
+        self.schemas.get(&self.current_schema_key())
+    }
+
+    pub fn content_type(&self) -> Option<String> {
+        self.parsed_headers()
+            .into_iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case("content-type"))
+            .map(|(_, value)| value)
+    }
+
+    pub fn edit_current_buffer(&mut self) -> std::io::Result<()> {
+        if let Some(hook) = self.editing_script.take() {
+            let script = match hook {
+                Hook::Pre => &mut self.current_request.pre_script,
+                Hook::Post => &mut self.current_request.post_script,
+            };
+            *script = editor::edit_text(script, &format!("{}-script", hook.name()), "rhai")?;
+            return Ok(());
+        }
+        let (name, extension) = match self.active_panel {
+            Panel::Url => ("url", "txt"),
+            Panel::Params => ("params", "txt"),
+            Panel::Headers => ("headers", "txt"),
+            Panel::Variables => ("variables", "json"),
+            Panel::Body => (
+                "body",
+                match self.current_request.body_type {
+                    BodyType::Json => "json",
+                    BodyType::GraphQL => "graphql",
+                    BodyType::Raw => editor::extension_for_content_type(
+                        &self.content_type().unwrap_or_default(),
+                    ),
+                    _ => "txt",
+                },
+            ),
+            _ => return Ok(()),
+        };
+
+        let mut edited = editor::edit_text(self.current_buffer(), name, extension)?;
+        if self.active_panel == Panel::Url {
+            edited = edited.lines().map(str::trim).collect();
+        }
+
+        let cursor = buffer::clamp(&edited, self.cursor());
+        if let Some(buffer) = self.current_buffer_mut() {
+            *buffer = edited;
+        }
+        self.set_cursor(cursor);
+        Ok(())
+    }
+
+    pub fn move_cursor_left(&mut self) {
+        let cursor = buffer::prev_boundary(self.current_buffer(), self.cursor());
+        self.set_cursor(cursor);
+    }
+
+    pub fn move_cursor_right(&mut self) {
+        let cursor = buffer::next_boundary(self.current_buffer(), self.cursor());
+        self.set_cursor(cursor);
+    }
+
     pub fn move_cursor_down(&mut self) {
         let buffer = self.current_buffer();
         let cursor = self.cursor();
@@ -176,8 +1572,8 @@ impl App {
         let lines: Vec<&str> = buffer.lines().collect();
 
         if current_line + 1 < lines.len() {
-            let next_line = lines[current_line + 1];
-            let new_col = current_col.min(next_line.len());
+            let width = buffer::display_width(&lines[current_line][..current_col]);
+            let new_col = buffer::col_for_width(lines[current_line + 1], width);
             let new_cursor = self.line_col_to_cursor(buffer, current_line + 1, new_col);
             self.set_cursor(new_cursor);
         }
@@ -190,8 +1586,8 @@ impl App {
 
         if current_line > 0 {
             let lines: Vec<&str> = buffer.lines().collect();
-            let prev_line = lines[current_line - 1];
-            let new_col = current_col.min(prev_line.len());
+            let width = buffer::display_width(&lines[current_line][..current_col]);
+            let new_col = buffer::col_for_width(lines[current_line - 1], width);
             let new_cursor = self.line_col_to_cursor(buffer, current_line - 1, new_col);
             self.set_cursor(new_cursor);
         }
@@ -228,69 +1624,19 @@ impl App {
     }
 
     pub fn move_word_forward(&mut self) {
-        let buffer = self.current_buffer();
-        let mut cursor = self.cursor();
-        let chars: Vec<char> = buffer.chars().collect();
-
-        if cursor >= chars.len() {
-            return;
-        }
-
-        let current_char = chars[cursor];
-        let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
-
-        if is_word_char(current_char) {
-            while cursor < chars.len() && is_word_char(chars[cursor]) {
-                cursor += 1;
-            }
-        } else if !current_char.is_whitespace() {
-            while cursor < chars.len()
-            && !chars[cursor].is_whitespace()
-            && !is_word_char(chars[cursor]) {
-                cursor += 1;
-            }
-        }
-
-        while cursor < chars.len() && chars[cursor].is_whitespace() {
-            cursor += 1;
-        }
-
+        let cursor = buffer::word_forward(self.current_buffer(), self.cursor());
         self.set_cursor(cursor);
     }
 
     pub fn move_word_backwards(&mut self) {
-        let buffer = self.current_buffer();
-        let mut cursor = self.cursor();
-
-        if cursor == 0 {
-            return;
-        }
-
-        let chars: Vec<char> = buffer.chars().collect();
-        cursor = cursor.saturating_sub(1);
-        let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
-
-        while cursor > 0 && cursor < chars.len() && chars[cursor].is_whitespace() {
-            cursor -= 1;
-        }
-
-        if cursor < chars.len() && !chars[cursor].is_whitespace() {
-            let in_word = is_word_char(chars[cursor]);
-
-            while cursor > 0 {
-                let prev_char = chars[cursor - 1];
-                if is_word_char(prev_char) != in_word || prev_char.is_whitespace() {
-                    break;
-                }
-                cursor -= 1;
-            }
-        }
-
+        let cursor = buffer::word_backward(self.current_buffer(), self.cursor());
         self.set_cursor(cursor);
     }
 
+    // The Sidebar lists saved collections followed by the loaded gRPC methods.
     pub fn select_next_collection(&mut self) {
-        if !self.collections.is_empty() && self.selected_collection_index < self.collections.len() - 1 {
+        let len = self.collections.len() + self.grpc_methods.len();
+        if len > 0 && self.selected_collection_index < len - 1 {
             self.selected_collection_index += 1;
         }
     }
@@ -304,6 +1650,13 @@ impl App {
     pub fn selected_collection(&self) -> Option<&str> {
         self.collections.get(self.selected_collection_index).map(|s| s.as_str())
     }
+
+    pub fn selected_grpc_method(&self) -> Option<&str> {
+        self.selected_collection_index
+            .checked_sub(self.collections.len())
+            .and_then(|i| self.grpc_methods.get(i))
+            .map(|s| s.as_str())
+    }
 }
 
 impl Default for App {
@@ -311,3 +1664,30 @@ impl Default for App {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn editing_the_url_updates_params() {
+        let mut app = App::new();
+        app.params_buffer = "# debug = 1".to_string();
+        app.synced_params = app.params_buffer.clone();
+        app.url_buffer = "https://api.example.com/users?page=2".to_string();
+        app.sync_query_params();
+        assert_eq!(app.params_buffer, "# debug = 1\npage = 2");
+        assert_eq!(app.url_buffer, "https://api.example.com/users?page=2");
+    }
+
+    #[test]
+    fn editing_params_updates_the_url() {
+        let mut app = App::new();
+        app.url_buffer = "https://api.example.com/users?page=2#list".to_string();
+        app.sync_query_params();
+        app.params_buffer = "page = 3\n# debug = 1\nq = a&b".to_string();
+        app.sync_query_params();
+        assert_eq!(app.url_buffer, "https://api.example.com/users?page=3&q=a%26b#list");
+        assert_eq!(app.disabled_params().len(), 1);
+    }
+}
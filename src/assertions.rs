@@ -0,0 +1,274 @@
+use crate::http::Response;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use serde_json_path::JsonPath;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Check {
+    Exists,
+    Equals(String),
+    Matches(String),
+}
+
+// Saved in their text form, e.g. "status == 200" or "json $.user.id exists".
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Assertion {
+    Status(u16),
+    StatusRange(u16, u16),
+    Header(String, Check),
+    Json(String, Check),
+    Time(u128),
+    Schema(String),
+}
+
+impl std::fmt::Display for Check {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Check::Exists => write!(f, "exists"),
+            Check::Equals(value) => write!(f, "== {}", value),
+            Check::Matches(pattern) => write!(f, "~ {}", pattern),
+        }
+    }
+}
+
+impl std::fmt::Display for Assertion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Assertion::Status(status) => write!(f, "status == {}", status),
+            Assertion::StatusRange(from, to) => write!(f, "status in {}..{}", from, to),
+            Assertion::Header(name, check) => write!(f, "header {} {}", name, check),
+            Assertion::Json(path, check) => write!(f, "json {} {}", path, check),
+            Assertion::Time(ms) => write!(f, "time < {}", ms),
+            Assertion::Schema(path) => write!(f, "schema {}", path),
+        }
+    }
+}
+
+fn parse_check(text: &str) -> Result<Check, String> {
+    let text = text.trim();
+    if text.is_empty() || text == "exists" {
+        return Ok(Check::Exists);
+    }
+    if let Some(value) = text.strip_prefix("==") {
+        return Ok(Check::Equals(value.trim().to_string()));
+    }
+    if let Some(pattern) = text.strip_prefix('~') {
+        let pattern = pattern.trim();
+        regex::Regex::new(pattern).map_err(|e| format!("invalid regex: {}", e))?;
+        return Ok(Check::Matches(pattern.to_string()));
+    }
+    Err(format!("expected exists, == <value> or ~ <regex>, got: {}", text))
+}
+
+fn number<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("expected a number, got: {}", value.trim()))
+}
+
+impl std::str::FromStr for Assertion {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let text = text.trim();
+        let (kind, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        let rest = rest.trim();
+        // The header name or JSONPath is the first word, so paths can't contain spaces.
+        let (target, check) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+
+        match kind {
+            "status" => match (target, check) {
+                ("==", status) => Ok(Assertion::Status(number(status)?)),
+                ("in", range) => {
+                    let (from, to) = range
+                        .split_once("..")
+                        .ok_or_else(|| format!("expected <from>..<to>, got: {}", range))?;
+                    Ok(Assertion::StatusRange(number(from)?, number(to)?))
+                }
+                _ => Err("usage: status == <code> | status in <from>..<to>".to_string()),
+            },
+            "header" if !target.is_empty() => Ok(Assertion::Header(target.to_string(), parse_check(check)?)),
+            "json" if !target.is_empty() => {
+                JsonPath::parse(target).map_err(|e| format!("invalid JSONPath: {}", e))?;
+                Ok(Assertion::Json(target.to_string(), parse_check(check)?))
+            }
+            "time" if target == "<" => Ok(Assertion::Time(number(check)?)),
+            "schema" if !rest.is_empty() => Ok(Assertion::Schema(rest.to_string())),
+            _ => Err(format!("unknown assertion: {}", text)),
+        }
+    }
+}
+
+impl TryFrom<String> for Assertion {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        text.parse()
+    }
+}
+
+impl From<Assertion> for String {
+    fn from(assertion: Assertion) -> String {
+        assertion.to_string()
+    }
+}
+
+fn check_value(actual: &str, check: &Check) -> Result<(), String> {
+    match check {
+        Check::Exists => Ok(()),
+        Check::Equals(expected) if actual == expected => Ok(()),
+        Check::Equals(expected) => Err(format!("expected {}, got {}", expected, actual)),
+        Check::Matches(pattern) => {
+            let regex = regex::Regex::new(pattern).map_err(|e| e.to_string())?;
+            if regex.is_match(actual) {
+                Ok(())
+            } else {
+                Err(format!("{} does not match {}", actual, pattern))
+            }
+        }
+    }
+}
+
+fn parse_body(response: &Response) -> Result<Value, String> {
+    serde_json::from_str(&response.body).map_err(|e| format!("body is not JSON: {}", e))
+}
+
+impl Assertion {
+    // Err carries the reason the response failed the assertion.
+    pub fn check(&self, response: &Response) -> Result<(), String> {
+        match self {
+            Assertion::Status(status) if response.status == *status => Ok(()),
+            Assertion::Status(_) => Err(format!("got status {}", response.status)),
+            Assertion::StatusRange(from, to) if (*from..=*to).contains(&response.status) => Ok(()),
+            Assertion::StatusRange(..) => Err(format!("got status {}", response.status)),
+            Assertion::Header(name, check) => match response.header(name) {
+                Some(value) => check_value(value, check),
+                None => Err(format!("no {} header", name)),
+            },
+            Assertion::Json(path, check) => {
+                let body = parse_body(response)?;
+                let path = JsonPath::parse(path).map_err(|e| e.to_string())?;
+                let Some(node) = path.query(&body).first() else {
+                    return Err("no match".to_string());
+                };
+                match check {
+                    // Compared as JSON, so `== 42` and `== "42"` differ; bare words are strings.
+                    Check::Equals(expected) => {
+                        let expected = serde_json::from_str(expected)
+                            .unwrap_or_else(|_| Value::String(expected.clone()));
+                        if *node == expected {
+                            Ok(())
+                        } else {
+                            Err(format!("expected {}, got {}", expected, node))
+                        }
+                    }
+                    check => match node {
+                        Value::String(text) => check_value(text, check),
+                        node => check_value(&node.to_string(), check),
+                    },
+                }
+            }
+            Assertion::Time(limit) if response.duration_ms < *limit => Ok(()),
+            Assertion::Time(_) => Err(format!("took {} ms", response.duration_ms)),
+            Assertion::Schema(path) => {
+                let path = match path.strip_prefix("~/") {
+                    Some(rest) => format!("{}/{}", std::env::var("HOME").unwrap_or_default(), rest),
+                    None => path.clone(),
+                };
+                let schema = std::fs::read_to_string(&path)
+                    .map_err(|e| format!("cannot read {}: {}", path, e))?;
+                let schema: Value = serde_json::from_str(&schema)
+                    .map_err(|e| format!("{} is not JSON: {}", path, e))?;
+                let validator = jsonschema::validator_for(&schema)
+                    .map_err(|e| format!("invalid schema {}: {}", path, e))?;
+                let body = parse_body(response)?;
+                let errors: Vec<String> = validator
+                    .iter_errors(&body)
+                    .map(|e| match e.instance_path.to_string() {
+                        location if location.is_empty() => e.to_string(),
+                        location => format!("{} at {}", e, location),
+                    })
+                    .collect();
+                if errors.is_empty() {
+                    Ok(())
+                } else {
+                    Err(errors.join("; "))
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response() -> Response {
+        Response {
+            status: 201,
+            headers: vec![(
+                "Content-Type".to_string(),
+                "application/json; charset=utf-8".to_string(),
+            )],
+            body: r#"{"user": {"id": 42, "name": "Ada", "code": "42"}}"#.to_string(),
+            duration_ms: 120,
+            ..Response::default()
+        }
+    }
+
+    #[test]
+    fn assertions_round_trip_through_text() {
+        for text in [
+            "status == 200",
+            "status in 200..299",
+            "header content-type exists",
+            "header content-type ~ ^application/json",
+            "json $.user.id == 42",
+            "json $.user.name exists",
+            "time < 500",
+            "schema ~/schemas/user.json",
+        ] {
+            let assertion: Assertion = text.parse().unwrap();
+            assert_eq!(assertion.to_string(), text);
+        }
+        // A bare target checks that it exists.
+        assert_eq!("json $.id".parse::<Assertion>().unwrap().to_string(), "json $.id exists");
+    }
+
+    #[test]
+    fn invalid_assertions_are_rejected() {
+        for text in [
+            "status 200",
+            "status in 200",
+            "json",
+            "json $..[ exists",
+            "header x ~ (",
+            "time > 5",
+            "nope",
+        ] {
+            assert!(text.parse::<Assertion>().is_err(), "{} parsed", text);
+        }
+    }
+
+    #[test]
+    fn checks_against_a_response() {
+        let response = response();
+        let check = |text: &str| text.parse::<Assertion>().unwrap().check(&response);
+        assert!(check("status == 201").is_ok());
+        assert_eq!(check("status == 200"), Err("got status 201".to_string()));
+        assert!(check("status in 200..299").is_ok());
+        assert!(check("header Content-Type ~ json").is_ok());
+        assert_eq!(check("header x-missing exists"), Err("no x-missing header".to_string()));
+        assert!(check("json $.user.id == 42").is_ok());
+        // JSON comparison tells numbers and strings apart.
+        assert!(check("json $.user.code == 42").is_err());
+        assert!(check(r#"json $.user.code == "42""#).is_ok());
+        assert!(check("json $.user.name == Ada").is_ok());
+        assert!(check("json $.user.id ~ ^4").is_ok());
+        assert_eq!(check("json $.user.email exists"), Err("no match".to_string()));
+        assert!(check("time < 200").is_ok());
+        assert_eq!(check("time < 100"), Err("took 120 ms".to_string()));
+    }
+}
@@ -0,0 +1,325 @@
+use crate::oauth::OAuth2Config;
+use crate::sigv4::Credentials;
+use md5::Md5;
+use rand::Rng;
+use reqwest::blocking::RequestBuilder;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiKeyLocation {
+    #[default]
+    Header,
+    Query,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Auth {
+    #[default]
+    None,
+    Basic {
+        username: String,
+        password: String,
+    },
+    Bearer {
+        token: String,
+    },
+    Digest {
+        username: String,
+        password: String,
+    },
+    ApiKey {
+        name: String,
+        value: String,
+        location: ApiKeyLocation,
+    },
+    OAuth2(OAuth2Config),
+    Aws {
+        access_key: String,
+        secret_key: String,
+        #[serde(default)]
+        session_token: String,
+        region: String,
+        service: String,
+    },
+}
+
+// Unresolved placeholders fall back to the standard AWS environment variables.
+fn aws_value(value: &str, env_var: &str) -> String {
+    if value.is_empty() || value.contains("{{") {
+        std::env::var(env_var).unwrap_or_default()
+    } else {
+        value.to_string()
+    }
+}
+
+fn aws(service: &str, region: &str) -> Auth {
+    Auth::Aws {
+        access_key: "{{aws_access_key_id}}".to_string(),
+        secret_key: "{{aws_secret_access_key}}".to_string(),
+        session_token: "{{aws_session_token}}".to_string(),
+        region: region.to_string(),
+        service: service.to_string(),
+    }
+}
+
+impl Auth {
+    pub const KINDS: [&str; 7] = ["none", "basic", "bearer", "digest", "apikey", "oauth2", "aws"];
+
+    pub fn is_none(&self) -> bool {
+        *self == Auth::None
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Auth::None => "none",
+            Auth::Basic { .. } => "basic",
+            Auth::Bearer { .. } => "bearer",
+            Auth::Digest { .. } => "digest",
+            Auth::ApiKey { .. } => "apikey",
+            Auth::OAuth2(_) => "oauth2",
+            Auth::Aws { .. } => "aws",
+        }
+    }
+
+    // Passwords and keys run to the end of the line, so they may contain spaces.
+    pub fn parse(args: &str) -> Result<Auth, String> {
+        let invalid = || format!("invalid auth: {}", args);
+        let args = args.trim();
+        let (kind, rest) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+        let rest = rest.trim_start();
+        // The first word, and everything after it as written.
+        let (first, secret) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        let secret = secret.trim_start().to_string();
+        match kind {
+            "basic" if !first.is_empty() => Ok(Auth::Basic {
+                username: first.to_string(),
+                password: secret,
+            }),
+            "bearer" if !rest.is_empty() => Ok(Auth::Bearer { token: rest.to_string() }),
+            "digest" if !secret.is_empty() => Ok(Auth::Digest {
+                username: first.to_string(),
+                password: secret,
+            }),
+            "apikey" if !secret.is_empty() => {
+                let (value, location) = match secret.rsplit_once(char::is_whitespace) {
+                    Some((value, "query")) => (value.trim_end(), ApiKeyLocation::Query),
+                    Some((value, "header")) => (value.trim_end(), ApiKeyLocation::Header),
+                    _ => (secret.as_str(), ApiKeyLocation::Header),
+                };
+                Ok(Auth::ApiKey {
+                    name: first.to_string(),
+                    value: value.to_string(),
+                    location,
+                })
+            }
+            _ => {
+                let parts: Vec<&str> = args.split_whitespace().collect();
+                match parts.as_slice() {
+                    ["none"] => Ok(Auth::None),
+                    ["oauth2"] => Ok(Auth::OAuth2(OAuth2Config::default())),
+                    ["oauth2", grant] => Ok(Auth::OAuth2(OAuth2Config {
+                        grant: grant.parse()?,
+                        ..OAuth2Config::default()
+                    })),
+                    ["aws"] => Ok(aws("{{aws_service}}", "{{aws_region}}")),
+                    ["aws", service] => Ok(aws(service, "{{aws_region}}")),
+                    ["aws", service, region] => Ok(aws(service, region)),
+                    _ => Err(invalid()),
+                }
+            }
+        }
+    }
+
+    pub fn map_values(&self, f: impl Fn(&str) -> String) -> Auth {
+        match self {
+            Auth::None => Auth::None,
+            Auth::Basic { username, password } => Auth::Basic {
+                username: f(username),
+                password: f(password),
+            },
+            Auth::Bearer { token } => Auth::Bearer { token: f(token) },
+            Auth::Digest { username, password } => Auth::Digest {
+                username: f(username),
+                password: f(password),
+            },
+            Auth::ApiKey { name, value, location } => Auth::ApiKey {
+                name: f(name),
+                value: f(value),
+                location: *location,
+            },
+            Auth::OAuth2(config) => Auth::OAuth2(config.map_values(f)),
+            Auth::Aws { access_key, secret_key, session_token, region, service } => Auth::Aws {
+                access_key: f(access_key),
+                secret_key: f(secret_key),
+                session_token: f(session_token),
+                region: f(region),
+                service: f(service),
+            },
+        }
+    }
+
+    // Returns the credentials, region and service to sign with, once variables are resolved.
+    pub fn aws_signing(&self) -> Option<(Credentials, String, String)> {
+        let Auth::Aws { access_key, secret_key, session_token, region, service } = self else {
+            return None;
+        };
+        let session_token = aws_value(session_token, "AWS_SESSION_TOKEN");
+        let region = match aws_value(region, "AWS_REGION") {
+            region if region.is_empty() => aws_value("", "AWS_DEFAULT_REGION"),
+            region => region,
+        };
+        let credentials = Credentials {
+            access_key: aws_value(access_key, "AWS_ACCESS_KEY_ID"),
+            secret_key: aws_value(secret_key, "AWS_SECRET_ACCESS_KEY"),
+            session_token: (!session_token.is_empty()).then_some(session_token),
+        };
+        Some((credentials, region, service.clone()))
+    }
+
+    // Digest needs the server's challenge first, so it is applied by `digest_authorization`.
+    // OAuth2 is swapped for a Bearer token by the app before the request is sent.
+    // AWS signs the finished request, so it is applied by `sigv4::sign_request`.
+    pub fn apply(&self, req_builder: RequestBuilder) -> RequestBuilder {
+        match self {
+            Auth::None | Auth::Digest { .. } | Auth::OAuth2(_) | Auth::Aws { .. } => req_builder,
+            Auth::Basic { username, password } => {
+                req_builder.basic_auth(username, Some(password))
+            }
+            Auth::Bearer { token } => req_builder.bearer_auth(token),
+            Auth::ApiKey { name, value, location: ApiKeyLocation::Header } => {
+                req_builder.header(name, value)
+            }
+            Auth::ApiKey { name, value, location: ApiKeyLocation::Query } => {
+                req_builder.query(&[(name, value)])
+            }
+        }
+    }
+}
+
+fn parse_challenge(header: &str) -> Option<HashMap<String, String>> {
+    let rest = header.trim();
+    let (scheme, params) = rest.split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("digest") {
+        return None;
+    }
+
+    let mut values = HashMap::new();
+    let mut rest = params.trim();
+    while !rest.is_empty() {
+        let (key, after) = rest.split_once('=')?;
+        let after = after.trim_start();
+        let (value, remaining) = if let Some(quoted) = after.strip_prefix('"') {
+            let end = quoted.find('"')?;
+            (&quoted[..end], &quoted[end + 1..])
+        } else {
+            let end = after.find(',').unwrap_or(after.len());
+            (after[..end].trim(), &after[end..])
+        };
+        values.insert(key.trim().to_ascii_lowercase(), value.to_string());
+        rest = remaining.trim_start().trim_start_matches(',').trim_start();
+    }
+    Some(values)
+}
+
+fn hash(algorithm: &str, data: &str) -> String {
+    let bytes: Vec<u8> = if algorithm.to_ascii_uppercase().starts_with("SHA-256") {
+        Sha256::digest(data.as_bytes()).to_vec()
+    } else {
+        Md5::digest(data.as_bytes()).to_vec()
+    };
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn digest_authorization(
+    challenge: &str,
+    username: &str,
+    password: &str,
+    method: &str,
+    uri: &str,
+) -> Option<String> {
+    let params = parse_challenge(challenge)?;
+    let realm = params.get("realm").cloned().unwrap_or_default();
+    let nonce = params.get("nonce")?.clone();
+    let algorithm = params
+        .get("algorithm")
+        .cloned()
+        .unwrap_or_else(|| "MD5".to_string());
+    let qop = params.get("qop").and_then(|qop| {
+        qop.split(',')
+            .map(str::trim)
+            .find(|q| q.eq_ignore_ascii_case("auth"))
+            .map(str::to_string)
+    });
+
+    let cnonce: String = format!("{:016x}", rand::thread_rng().r#gen::<u64>());
+    let nc = "00000001";
+
+    let mut ha1 = hash(&algorithm, &format!("{}:{}:{}", username, realm, password));
+    if algorithm.to_ascii_lowercase().ends_with("-sess") {
+        ha1 = hash(&algorithm, &format!("{}:{}:{}", ha1, nonce, cnonce));
+    }
+    let ha2 = hash(&algorithm, &format!("{}:{}", method, uri));
+    let response = match &qop {
+        Some(qop) => hash(
+            &algorithm,
+            &format!("{}:{}:{}:{}:{}:{}", ha1, nonce, nc, cnonce, qop, ha2),
+        ),
+        None => hash(&algorithm, &format!("{}:{}:{}", ha1, nonce, ha2)),
+    };
+
+    let mut header = format!(
+        "Digest username=\"{}\", realm=\"{}\", nonce=\"{}\", uri=\"{}\", algorithm={}, response=\"{}\"",
+        username, realm, nonce, uri, algorithm, response
+    );
+    if let Some(qop) = qop {
+        header.push_str(&format!(", qop={}, nc={}, cnonce=\"{}\"", qop, nc, cnonce));
+    }
+    if let Some(opaque) = params.get("opaque") {
+        header.push_str(&format!(", opaque=\"{}\"", opaque));
+    }
+    Some(header)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secrets_keep_their_spaces() {
+        let basic = Auth::Basic {
+            username: "alice".to_string(),
+            password: "correct horse  battery".to_string(),
+        };
+        assert_eq!(Auth::parse("basic alice correct horse  battery"), Ok(basic));
+        assert_eq!(
+            Auth::parse("bearer abc def"),
+            Ok(Auth::Bearer { token: "abc def".to_string() })
+        );
+    }
+
+    #[test]
+    fn apikey_location_is_the_last_word() {
+        let key = |value: &str, location| Auth::ApiKey {
+            name: "X-Key".to_string(),
+            value: value.to_string(),
+            location,
+        };
+        assert_eq!(Auth::parse("apikey X-Key a b"), Ok(key("a b", ApiKeyLocation::Header)));
+        assert_eq!(Auth::parse("apikey X-Key a b query"), Ok(key("a b", ApiKeyLocation::Query)));
+    }
+
+    #[test]
+    fn incomplete_auth_is_rejected() {
+        for args in ["basic", "bearer", "digest alice", "apikey X-Key", "none extra", "aws a b c"] {
+            assert!(Auth::parse(args).is_err(), "{}", args);
+        }
+        assert_eq!(
+            Auth::parse("basic alice"),
+            Ok(Auth::Basic { username: "alice".to_string(), password: String::new() })
+        );
+    }
+}
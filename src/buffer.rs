@@ -0,0 +1,206 @@
+// Cursors are byte offsets that always sit on a grapheme boundary, so they
+// can be used to slice the buffer directly.
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+pub fn next_boundary(text: &str, cursor: usize) -> usize {
+    let cursor = clamp(text, cursor);
+    text[cursor..]
+        .graphemes(true)
+        .next()
+        .map_or(cursor, |g| cursor + g.len())
+}
+
+pub fn prev_boundary(text: &str, cursor: usize) -> usize {
+    let cursor = clamp(text, cursor);
+    text[..cursor]
+        .graphemes(true)
+        .next_back()
+        .map_or(cursor, |g| cursor - g.len())
+}
+
+pub fn clamp(text: &str, cursor: usize) -> usize {
+    if cursor >= text.len() {
+        return text.len();
+    }
+    text.grapheme_indices(true)
+        .map(|(i, _)| i)
+        .take_while(|&i| i <= cursor)
+        .last()
+        .unwrap_or(0)
+}
+
+pub fn display_width(text: &str) -> usize {
+    UnicodeWidthStr::width(text)
+}
+
+pub fn col_for_width(line: &str, width: usize) -> usize {
+    let mut used = 0;
+    for (i, g) in line.grapheme_indices(true) {
+        let w = display_width(g);
+        if used + w > width {
+            return i;
+        }
+        used += w;
+    }
+    line.len()
+}
+
+pub fn insert_char(buffer: &mut String, cursor: usize, c: char) -> usize {
+    let cursor = clamp(buffer, cursor);
+    buffer.insert(cursor, c);
+    // A combining mark joins the previous grapheme, so land after the whole cluster.
+    next_boundary(buffer, prev_boundary(buffer, cursor + c.len_utf8()))
+}
+
+pub fn delete_before(buffer: &mut String, cursor: usize) -> usize {
+    let end = clamp(buffer, cursor);
+    let start = prev_boundary(buffer, end);
+    buffer.replace_range(start..end, "");
+    start
+}
+
+pub fn delete_at(buffer: &mut String, cursor: usize) {
+    let start = clamp(buffer, cursor);
+    let end = next_boundary(buffer, start);
+    buffer.replace_range(start..end, "");
+}
+
+fn is_word(g: &str) -> bool {
+    g.chars().next().is_some_and(|c| c.is_alphanumeric() || c == '_')
+}
+
+fn is_space(g: &str) -> bool {
+    g.chars().all(char::is_whitespace)
+}
+
+pub fn word_forward(text: &str, cursor: usize) -> usize {
+    let cursor = clamp(text, cursor);
+    let mut graphemes = text[cursor..].grapheme_indices(true).peekable();
+    let Some(&(_, first)) = graphemes.peek() else {
+        return cursor;
+    };
+
+    if !is_space(first) {
+        let in_word = is_word(first);
+        while let Some(&(_, g)) = graphemes.peek() {
+            if is_space(g) || is_word(g) != in_word {
+                break;
+            }
+            graphemes.next();
+        }
+    }
+
+    while let Some(&(_, g)) = graphemes.peek() {
+        if !is_space(g) {
+            break;
+        }
+        graphemes.next();
+    }
+
+    graphemes.peek().map_or(text.len(), |&(i, _)| cursor + i)
+}
+
+pub fn word_backward(text: &str, cursor: usize) -> usize {
+    let cursor = clamp(text, cursor);
+    let mut graphemes = text[..cursor].grapheme_indices(true).rev().peekable();
+
+    while let Some(&(_, g)) = graphemes.peek() {
+        if !is_space(g) {
+            break;
+        }
+        graphemes.next();
+    }
+
+    let Some((mut pos, first)) = graphemes.next() else {
+        return 0;
+    };
+    let in_word = is_word(first);
+    for (i, g) in graphemes {
+        if is_space(g) || is_word(g) != in_word {
+            break;
+        }
+        pos = i;
+    }
+    pos
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // "e" followed by a combining acute accent is one grapheme of three bytes.
+    const ACCENT: &str = "e\u{301}";
+
+    #[test]
+    fn boundaries_step_over_whole_graphemes() {
+        let text = format!("a{ACCENT}b");
+        assert_eq!(next_boundary(&text, 0), 1);
+        assert_eq!(next_boundary(&text, 1), 4);
+        assert_eq!(prev_boundary(&text, 4), 1);
+        assert_eq!(prev_boundary(&text, 0), 0);
+        assert_eq!(next_boundary(&text, text.len()), text.len());
+
+        let emoji = "👍🏽x";
+        assert_eq!(next_boundary(emoji, 0), "👍🏽".len());
+    }
+
+    #[test]
+    fn clamp_snaps_into_the_grapheme_start() {
+        let text = format!("a{ACCENT}b");
+        assert_eq!(clamp(&text, 2), 1);
+        assert_eq!(clamp(&text, 3), 1);
+        assert_eq!(clamp(&text, 4), 4);
+        assert_eq!(clamp(&text, 100), text.len());
+    }
+
+    #[test]
+    fn widths_count_wide_characters_twice() {
+        assert_eq!(display_width("日本"), 4);
+        assert_eq!(display_width(ACCENT), 1);
+        assert_eq!(col_for_width("日本語", 3), "日".len());
+        assert_eq!(col_for_width("日本語", 4), "日本".len());
+        assert_eq!(col_for_width("ab", 10), 2);
+    }
+
+    #[test]
+    fn combining_mark_lands_after_the_cluster() {
+        let mut buffer = "eb".to_string();
+        let cursor = insert_char(&mut buffer, 1, '\u{301}');
+        assert_eq!(buffer, format!("{ACCENT}b"));
+        assert_eq!(cursor, ACCENT.len());
+    }
+
+    #[test]
+    fn deletes_remove_whole_graphemes() {
+        let mut buffer = format!("a{ACCENT}b");
+        assert_eq!(delete_before(&mut buffer, 4), 1);
+        assert_eq!(buffer, "ab");
+
+        let mut buffer = format!("a{ACCENT}b");
+        delete_at(&mut buffer, 1);
+        assert_eq!(buffer, "ab");
+    }
+
+    #[test]
+    fn word_motions_skip_punctuation_runs_and_spaces() {
+        let text = "foo.bar  baz";
+        assert_eq!(word_forward(text, 0), 3);
+        assert_eq!(word_forward(text, 3), 4);
+        assert_eq!(word_forward(text, 4), 9);
+        assert_eq!(word_forward(text, 9), text.len());
+        assert_eq!(word_backward(text, text.len()), 9);
+        assert_eq!(word_backward(text, 9), 4);
+        assert_eq!(word_backward(text, 4), 3);
+        assert_eq!(word_backward(text, 3), 0);
+    }
+
+    #[test]
+    fn word_motions_treat_accented_letters_as_word() {
+        let text = format!("caf{ACCENT} ok");
+        let space = 3 + ACCENT.len();
+        assert_eq!(word_forward(&text, 0), space + 1);
+        assert_eq!(word_backward(&text, space + 1), 0);
+    }
+}
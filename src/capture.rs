@@ -0,0 +1,187 @@
+use crate::http::Response;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use serde_json_path::JsonPath;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    Json(String),
+    Regex(String),
+    Header(String),
+    Cookie(String),
+}
+
+// Saved in its text form, e.g. "auth_token = json $.token".
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Capture {
+    pub variable: String,
+    pub source: Source,
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Source::Json(path) => write!(f, "json {}", path),
+            Source::Regex(pattern) => write!(f, "regex {}", pattern),
+            Source::Header(name) => write!(f, "header {}", name),
+            Source::Cookie(name) => write!(f, "cookie {}", name),
+        }
+    }
+}
+
+impl std::fmt::Display for Capture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} = {}", self.variable, self.source)
+    }
+}
+
+impl std::str::FromStr for Capture {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let usage = || "usage: <variable> = json <path> | regex <pattern> | header <name> | cookie <name>";
+        let (variable, rest) = text.split_once('=').ok_or_else(usage)?;
+        let variable = variable.trim();
+        if variable.is_empty() || variable.contains(char::is_whitespace) || variable.contains("{{") {
+            return Err(format!("invalid variable name: {}", variable));
+        }
+        let rest = rest.trim();
+        let (kind, arg) = rest.split_once(char::is_whitespace).ok_or_else(usage)?;
+        let arg = arg.trim().to_string();
+        let source = match kind {
+            "json" => {
+                JsonPath::parse(&arg).map_err(|e| format!("invalid JSONPath: {}", e))?;
+                Source::Json(arg)
+            }
+            "regex" => {
+                regex::Regex::new(&arg).map_err(|e| format!("invalid regex: {}", e))?;
+                Source::Regex(arg)
+            }
+            "header" => Source::Header(arg),
+            "cookie" => Source::Cookie(arg),
+            _ => return Err(usage().to_string()),
+        };
+        Ok(Capture { variable: variable.to_string(), source })
+    }
+}
+
+impl TryFrom<String> for Capture {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        text.parse()
+    }
+}
+
+impl From<Capture> for String {
+    fn from(capture: Capture) -> String {
+        capture.to_string()
+    }
+}
+
+impl Capture {
+    // Err carries the reason nothing was captured.
+    pub fn extract(&self, response: &Response) -> Result<String, String> {
+        match &self.source {
+            Source::Json(path) => {
+                let body: Value = serde_json::from_str(&response.body)
+                    .map_err(|e| format!("body is not JSON: {}", e))?;
+                let path = JsonPath::parse(path).map_err(|e| e.to_string())?;
+                match path.query(&body).first() {
+                    Some(Value::String(text)) => Ok(text.clone()),
+                    Some(node) => Ok(node.to_string()),
+                    None => Err("no match".to_string()),
+                }
+            }
+            // The first capture group when there is one, otherwise the whole match.
+            Source::Regex(pattern) => {
+                let regex = regex::Regex::new(pattern).map_err(|e| e.to_string())?;
+                let captures = regex.captures(&response.body).ok_or("no match")?;
+                let found = captures.get(1).or_else(|| captures.get(0)).ok_or("no match")?;
+                Ok(found.as_str().to_string())
+            }
+            Source::Header(name) => response
+                .header(name)
+                .map(str::to_string)
+                .ok_or_else(|| format!("no {} header", name)),
+            Source::Cookie(name) => response
+                .headers
+                .iter()
+                .filter(|(k, _)| k.eq_ignore_ascii_case("set-cookie"))
+                .filter_map(|(_, v)| v.split(';').next()?.split_once('='))
+                .find(|(cookie, _)| cookie.trim() == name)
+                .map(|(_, value)| value.trim().to_string())
+                .ok_or_else(|| format!("no {} cookie", name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response() -> Response {
+        Response {
+            status: 200,
+            headers: vec![
+                ("Location".to_string(), "/users/7".to_string()),
+                ("Set-Cookie".to_string(), "theme=dark; Path=/".to_string()),
+                ("Set-Cookie".to_string(), "session=abc123; HttpOnly".to_string()),
+            ],
+            body: r#"{"token": "t0k", "user": {"id": 7}} order-99"#.to_string(),
+            ..Response::default()
+        }
+    }
+
+    #[test]
+    fn captures_round_trip_through_text() {
+        for text in [
+            "token = json $.token",
+            "order = regex order-(\\d+)",
+            "location = header Location",
+            "session = cookie session",
+        ] {
+            let capture: Capture = text.parse().unwrap();
+            assert_eq!(capture.to_string(), text);
+        }
+        let capture: Capture = "  id=json   $.id ".parse().unwrap();
+        assert_eq!(capture.variable, "id");
+        assert_eq!(capture.source, Source::Json("$.id".to_string()));
+    }
+
+    #[test]
+    fn invalid_captures_are_rejected() {
+        for text in [
+            "token json $.token",
+            "= json $.a",
+            "my var = json $.a",
+            "{{a}} = json $.a",
+            "a = json",
+            "a = xpath //a",
+            "a = regex (",
+        ] {
+            assert!(text.parse::<Capture>().is_err(), "{} parsed", text);
+        }
+    }
+
+    #[test]
+    fn extracts_from_a_response() {
+        // The trailing text makes the shared body invalid JSON, so JSON captures get their own.
+        let body = r#"{"token": "t0k", "user": {"id": 7}}"#.to_string();
+        let json = Response { body, ..response() };
+        let extract =
+            |text: &str, response: &Response| text.parse::<Capture>().unwrap().extract(response);
+        assert_eq!(extract("t = json $.token", &json), Ok("t0k".to_string()));
+        assert_eq!(extract("id = json $.user.id", &json), Ok("7".to_string()));
+        assert_eq!(extract("x = json $.missing", &json), Err("no match".to_string()));
+        assert!(extract("t = json $.token", &response()).is_err());
+
+        let response = response();
+        assert_eq!(extract("o = regex order-(\\d+)", &response), Ok("99".to_string()));
+        assert_eq!(extract("o = regex order-\\d+", &response), Ok("order-99".to_string()));
+        assert_eq!(extract("l = header location", &response), Ok("/users/7".to_string()));
+        assert_eq!(extract("s = cookie session", &response), Ok("abc123".to_string()));
+        assert_eq!(extract("s = cookie missing", &response), Err("no missing cookie".to_string()));
+    }
+}
@@ -0,0 +1,255 @@
+use crate::app::App;
+use crate::config::load_config;
+use crate::grpc;
+use crate::http::{DEFAULT_RESPONSE_LIMIT, Request, Response, describe_error, send_request};
+use crate::persistence::{
+    HistoryEntry, append_history, load_collection, load_environment, load_tokens,
+};
+use crate::runner;
+use crate::stream::{Stream, StreamMessage};
+use crate::websocket;
+use std::io::{self, Write};
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+
+// Exit codes: the request failed to send, or the server answered with an error status or
+// failed a script assertion (for `test`, a request failed to send or failed an assertion).
+const EXIT_ERROR: i32 = 2;
+const EXIT_HTTP_ERROR: i32 = 1;
+
+const USAGE: &str = "usage: vreq [run <request> [--env <name>] [--body]]
+       vreq test <collection> [--env <name>] [--junit <path>] [--data <csv|json>]
+                 [--iterations <n>] [--delay <ms>] [--stop-on-failure]";
+
+struct RunOptions {
+    name: String,
+    environment: Option<String>,
+    body_only: bool,
+}
+
+struct TestOptions {
+    name: String,
+    environment: Option<String>,
+    junit: Option<String>,
+    runner: runner::Options,
+}
+
+// Returns the exit code when the arguments ask for a headless command, or None to start the TUI.
+pub fn main(args: &[String]) -> Option<i32> {
+    let code = match args.first().map(String::as_str)? {
+        "run" => match parse_run(&args[1..]) {
+            Ok(options) => run(&options).unwrap_or_else(|err| {
+                eprintln!("vreq: {}", describe_error(err.as_ref()));
+                EXIT_ERROR
+            }),
+            Err(err) => {
+                eprintln!("vreq: {}\n{}", err, USAGE);
+                EXIT_ERROR
+            }
+        },
+        "test" => match parse_test(&args[1..]) {
+            Ok(options) => test(&options).unwrap_or_else(|err| {
+                eprintln!("vreq: {}", describe_error(err.as_ref()));
+                EXIT_ERROR
+            }),
+            Err(err) => {
+                eprintln!("vreq: {}\n{}", err, USAGE);
+                EXIT_ERROR
+            }
+        },
+        "-h" | "--help" | "help" => {
+            println!("{}", USAGE);
+            0
+        }
+        other => {
+            eprintln!("vreq: unknown command: {}\n{}", other, USAGE);
+            EXIT_ERROR
+        }
+    };
+    Some(code)
+}
+
+fn parse_run(args: &[String]) -> Result<RunOptions, String> {
+    let mut name = None;
+    let mut environment = None;
+    let mut body_only = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--env" | "-e" => {
+                environment = Some(args.next().ok_or("--env needs an environment name")?.clone());
+            }
+            "--body" | "-b" => body_only = true,
+            flag if flag.starts_with('-') => return Err(format!("unknown option: {}", flag)),
+            _ if name.is_some() => return Err(format!("unexpected argument: {}", arg)),
+            _ => name = Some(arg.clone()),
+        }
+    }
+    Ok(RunOptions {
+        name: name.ok_or("missing request name")?,
+        environment,
+        body_only,
+    })
+}
+
+fn parse_test(args: &[String]) -> Result<TestOptions, String> {
+    let mut name = None;
+    let mut environment = None;
+    let mut junit = None;
+    let mut runner = runner::Options::default();
+    let mut args = args.iter().map(String::as_str);
+    while let Some(arg) = args.next() {
+        match arg {
+            "--env" | "-e" => {
+                let value = args.next().ok_or("--env needs an environment name")?;
+                environment = Some(value.to_string());
+            }
+            "--junit" => junit = Some(args.next().ok_or("--junit needs a file path")?.to_string()),
+            _ if runner.take(arg, &mut args)? => {}
+            flag if flag.starts_with('-') => return Err(format!("unknown option: {}", flag)),
+            _ if name.is_some() => return Err(format!("unexpected argument: {}", arg)),
+            _ => name = Some(arg.to_string()),
+        }
+    }
+    Ok(TestOptions {
+        name: name.ok_or("missing collection name")?,
+        environment,
+        junit,
+        runner,
+    })
+}
+
+// Loads the request into an App so it is resolved exactly as the TUI would send it.
+fn headless_app(environment: Option<&str>) -> Result<App, Box<dyn std::error::Error>> {
+    let mut app = App::new();
+    app.config = load_config()?;
+    app.tokens = load_tokens()?;
+    if let Some(name) = environment {
+        app.active_environment = Some(load_environment(name)?);
+    }
+    Ok(app)
+}
+
+fn resolve(app: &mut App, name: &str) -> Result<Request, Box<dyn std::error::Error>> {
+    let name = name.strip_suffix(".json").unwrap_or(name);
+    let collection = load_collection(name)?;
+    app.apply_request(collection.saved_request.request);
+    app.request_name = Some(name.to_string());
+    app.resolve_request()
+}
+
+fn run(options: &RunOptions) -> Result<i32, Box<dyn std::error::Error>> {
+    let mut app = headless_app(options.environment.as_deref())?;
+    let request = resolve(&mut app, &options.name)?;
+
+    if websocket::is_websocket_url(&request.url) {
+        return Err("WebSocket requests can only be sent from the TUI".into());
+    }
+    let accepts_events = request.headers.iter().any(|h| {
+        h.enabled && h.name.eq_ignore_ascii_case("accept") && h.value.contains("text/event-stream")
+    });
+    if grpc::is_grpc_url(&request.url) {
+        let pool = match &app.descriptors {
+            Some((_, pool)) => pool.clone(),
+            None => grpc::load_descriptors(&request.proto)?,
+        };
+        return print_stream(grpc::call(request, &pool)?, options.body_only);
+    }
+    if accepts_events {
+        return print_stream(Stream::start(request), options.body_only);
+    }
+
+    let limit = app.config.response_limit.unwrap_or(DEFAULT_RESPONSE_LIMIT);
+    let response = send_request(&request, limit)?;
+    let _ = append_history(&HistoryEntry::new(
+        request.method.to_string(),
+        request.url.clone(),
+        response.status,
+        response.timing.clone(),
+    ));
+    for (capture, failure) in app.capture(&request, &response) {
+        if let Some(reason) = failure {
+            eprintln!("vreq: capture {}: {}", capture.variable, reason);
+        }
+    }
+    app.run_post_scripts(&request, &response);
+    for line in &app.script_output.logs {
+        eprintln!("{}", line);
+    }
+    for (assertion, failure) in &app.script_output.assertions {
+        if let Some(reason) = failure {
+            eprintln!("vreq: {}: {}", assertion, reason);
+        }
+    }
+
+    let mut stdout = io::stdout().lock();
+    if !options.body_only {
+        print_head(&mut stdout, &response)?;
+    }
+    match &response.body_file {
+        Some(path) => {
+            io::copy(&mut std::fs::File::open(path)?, &mut stdout)?;
+            let _ = std::fs::remove_file(path);
+        }
+        None => stdout.write_all(&response.raw)?,
+    }
+    stdout.flush()?;
+    if app.script_output.failures() > 0 {
+        return Ok(EXIT_HTTP_ERROR);
+    }
+    Ok(exit_code(&response))
+}
+
+// Exits with EXIT_HTTP_ERROR when any request fails to send or fails an assertion.
+fn test(options: &TestOptions) -> Result<i32, Box<dyn std::error::Error>> {
+    let mut app = headless_app(options.environment.as_deref())?;
+    let stop = AtomicBool::new(false);
+    let iterations = runner::run(&mut app, &options.name, &options.runner, &stop, &mut |_| {})?;
+    print!("{}", runner::summary_text(&iterations));
+    if let Some(path) = &options.junit {
+        std::fs::write(path, runner::junit_xml(&options.name, &iterations))
+            .map_err(|e| format!("cannot write {}: {}", path, e))?;
+    }
+    Ok(if iterations.iter().any(|i| i.failed() > 0) { EXIT_HTTP_ERROR } else { 0 })
+}
+
+fn print_head(out: &mut impl Write, response: &Response) -> io::Result<()> {
+    writeln!(out, "{}", response.status_text)?;
+    for (name, value) in &response.headers {
+        writeln!(out, "{}: {}", name, value)?;
+    }
+    writeln!(out)
+}
+
+fn exit_code(response: &Response) -> i32 {
+    if response.status >= 400 { EXIT_HTTP_ERROR } else { 0 }
+}
+
+// Prints streamed output as it arrives, as the Response panel would show it.
+fn print_stream(stream: Stream, body_only: bool) -> Result<i32, Box<dyn std::error::Error>> {
+    let mut stdout = io::stdout().lock();
+    let mut code = 0;
+    loop {
+        for message in stream.messages() {
+            match message {
+                StreamMessage::Started(response) => {
+                    if !body_only {
+                        print_head(&mut stdout, &response)?;
+                    }
+                    code = exit_code(&response);
+                }
+                StreamMessage::Text(text) => write!(stdout, "{}", text)?,
+                StreamMessage::Event(event) => {
+                    if !body_only && let Some(name) = &event.event {
+                        writeln!(stdout, "event: {}", name)?;
+                    }
+                    writeln!(stdout, "{}\n", event.data)?;
+                }
+                StreamMessage::Failed(err) => return Err(err.into()),
+                StreamMessage::Finished => return Ok(code),
+            }
+            stdout.flush()?;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+}
@@ -0,0 +1,137 @@
+use crate::persistence::get_base_dir;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::io::{IsTerminal, Write};
+use std::process::{Command, Stdio};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ClipboardBackend {
+    #[default]
+    Auto,
+    Osc52,
+    Wayland,
+    Xclip,
+    Xsel,
+    File,
+}
+
+pub fn copy(backend: ClipboardBackend, text: &str) {
+    let copied = match backend {
+        ClipboardBackend::Auto => {
+            // The file is only written when there is no clipboard program to read back from.
+            copy_with_program(text)
+                || (std::io::stdout().is_terminal() && copy_osc52_and_file(text))
+                || copy_file(text)
+        }
+        ClipboardBackend::Osc52 => copy_osc52_and_file(text),
+        ClipboardBackend::Wayland => pipe_to("wl-copy", &[], text),
+        ClipboardBackend::Xclip => pipe_to("xclip", &["-selection", "clipboard"], text),
+        ClipboardBackend::Xsel => pipe_to("xsel", &["--clipboard", "--input"], text),
+        ClipboardBackend::File => copy_file(text),
+    };
+    if !copied {
+        log!("clipboard: copy with {:?} failed", backend);
+    }
+}
+
+pub fn paste(backend: ClipboardBackend) -> Option<String> {
+    match backend {
+        ClipboardBackend::Auto => paste_with_program().or_else(paste_file),
+        // Terminals do not reliably answer OSC 52 queries, so reads go to the file.
+        ClipboardBackend::Osc52 | ClipboardBackend::File => paste_file(),
+        ClipboardBackend::Wayland => read_from("wl-paste", &["--no-newline"]),
+        ClipboardBackend::Xclip => read_from("xclip", &["-selection", "clipboard", "-o"]),
+        ClipboardBackend::Xsel => read_from("xsel", &["--clipboard", "--output"]),
+    }
+}
+
+fn copy_with_program(text: &str) -> bool {
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() && pipe_to("wl-copy", &[], text) {
+        return true;
+    }
+    if std::env::var_os("DISPLAY").is_some() {
+        return pipe_to("xclip", &["-selection", "clipboard"], text)
+            || pipe_to("xsel", &["--clipboard", "--input"], text);
+    }
+    false
+}
+
+fn paste_with_program() -> Option<String> {
+    if std::env::var_os("WAYLAND_DISPLAY").is_some()
+        && let Some(text) = read_from("wl-paste", &["--no-newline"])
+    {
+        return Some(text);
+    }
+    if std::env::var_os("DISPLAY").is_some() {
+        return read_from("xclip", &["-selection", "clipboard", "-o"])
+            .or_else(|| read_from("xsel", &["--clipboard", "--output"]));
+    }
+    None
+}
+
+fn pipe_to(program: &str, args: &[&str], text: &str) -> bool {
+    Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .and_then(|mut child| {
+            if let Some(stdin) = child.stdin.as_mut() {
+                stdin.write_all(text.as_bytes())?;
+            }
+            child.wait()
+        })
+        .is_ok_and(|status| status.success())
+}
+
+fn read_from(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8(output.stdout).ok()
+}
+
+fn copy_osc52(text: &str) -> bool {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(text);
+    let sequence = if std::env::var_os("TMUX").is_some() {
+        // tmux only forwards escape sequences wrapped in a DCS passthrough.
+        format!("\x1bPtmux;\x1b\x1b]52;c;{}\x07\x1b\\", encoded)
+    } else {
+        format!("\x1b]52;c;{}\x07", encoded)
+    };
+    let mut stdout = std::io::stdout();
+    stdout
+        .write_all(sequence.as_bytes())
+        .and_then(|_| stdout.flush())
+        .is_ok()
+}
+
+// OSC 52 can't be read back, so the file keeps a copy for "+p.
+fn copy_osc52_and_file(text: &str) -> bool {
+    let copied = copy_osc52(text);
+    copy_file(text) && copied
+}
+
+fn copy_file(text: &str) -> bool {
+    get_base_dir()
+        .ok()
+        .map(|mut path| {
+            path.push("clipboard");
+            std::fs::write(path, text).is_ok()
+        })
+        .unwrap_or(false)
+}
+
+fn paste_file() -> Option<String> {
+    let mut path = get_base_dir().ok()?;
+    path.push("clipboard");
+    std::fs::read_to_string(path).ok()
+}
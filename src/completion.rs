@@ -0,0 +1,168 @@
+use crate::app::{App, Panel};
+use crate::dynamic;
+use crate::http::BodyType;
+
+const HEADER_NAMES: &[&str] = &[
+    "Accept",
+    "Accept-Charset",
+    "Accept-Encoding",
+    "Accept-Language",
+    "Authorization",
+    "Cache-Control",
+    "Connection",
+    "Content-Disposition",
+    "Content-Encoding",
+    "Content-Language",
+    "Content-Length",
+    "Content-Type",
+    "Cookie",
+    "DNT",
+    "Expect",
+    "Forwarded",
+    "From",
+    "Host",
+    "If-Match",
+    "If-Modified-Since",
+    "If-None-Match",
+    "If-Range",
+    "If-Unmodified-Since",
+    "Origin",
+    "Pragma",
+    "Prefer",
+    "Range",
+    "Referer",
+    "TE",
+    "Upgrade",
+    "User-Agent",
+    "Via",
+    "X-Api-Key",
+    "X-Correlation-ID",
+    "X-Forwarded-For",
+    "X-Forwarded-Host",
+    "X-Forwarded-Proto",
+    "X-Request-ID",
+    "X-Requested-With",
+];
+
+const MEDIA_TYPES: &[&str] = &[
+    "application/json",
+    "application/xml",
+    "application/x-www-form-urlencoded",
+    "application/octet-stream",
+    "application/graphql",
+    "application/problem+json",
+    "multipart/form-data",
+    "text/plain",
+    "text/html",
+    "text/csv",
+    "text/event-stream",
+    "*/*",
+];
+
+fn header_values(name: &str) -> &'static [&'static str] {
+    match name.to_ascii_lowercase().as_str() {
+        "content-type" | "accept" => MEDIA_TYPES,
+        "accept-encoding" | "content-encoding" | "te" => {
+            &["gzip", "deflate", "br", "zstd", "identity", "*"]
+        }
+        "cache-control" => &[
+            "no-cache",
+            "no-store",
+            "max-age=0",
+            "max-age=3600",
+            "must-revalidate",
+            "no-transform",
+            "only-if-cached",
+            "private",
+            "public",
+        ],
+        "connection" => &["keep-alive", "close", "upgrade"],
+        "accept-language" => &["en-US", "en", "de", "fr", "es", "*"],
+        "authorization" => &["Bearer ", "Basic "],
+        "pragma" => &["no-cache"],
+        "prefer" => &["return=minimal", "return=representation", "respond-async"],
+        "x-requested-with" => &["XMLHttpRequest"],
+        _ => &[],
+    }
+}
+
+fn variable_names(app: &App) -> Vec<String> {
+    let mut names: Vec<String> = app
+        .active_environment
+        .as_ref()
+        .map(|env| env.variables.keys().cloned().collect())
+        .unwrap_or_default();
+    names.extend(dynamic::NAMES.iter().map(|n| n.to_string()));
+    names
+}
+
+fn unique(items: impl IntoIterator<Item = String>) -> Vec<String> {
+    let mut seen = Vec::new();
+    for item in items {
+        if !seen.contains(&item) {
+            seen.push(item);
+        }
+    }
+    seen
+}
+
+// Returns the byte offset in `line` where the completed word starts, and the
+// candidates that match the text between there and the cursor.
+pub fn candidates(app: &App, line: &str) -> Option<(usize, Vec<String>)> {
+    let history = &app.header_history;
+
+    if let Some(open) = line.rfind("{{")
+        && !line[open..].contains("}}")
+    {
+        let start = open + 2 + (line[open + 2..].len() - line[open + 2..].trim_start().len());
+        let prefix = &line[start..];
+        let names = variable_names(app)
+            .into_iter()
+            .filter(|n| n.starts_with(prefix))
+            .collect();
+        return Some((start, names));
+    }
+
+    if app.active_panel == Panel::Body && app.current_request.body_type == BodyType::GraphQL {
+        // The selection set depends on everything typed before the cursor.
+        let (prefix, fields) = app.schema()?.complete(&app.body_buffer[..app.cursor()])?;
+        return Some((line.len() - prefix.len(), fields));
+    }
+
+    if app.active_panel != Panel::Headers {
+        return None;
+    }
+
+    let offset = line.len() - line.trim_start_matches(['#', ' ']).len();
+    let line_text = &line[offset..];
+
+    match line_text.split_once(':') {
+        None => {
+            let prefix = line_text.to_ascii_lowercase();
+            let names = history
+                .iter()
+                .map(|h| h.name.clone())
+                .chain(HEADER_NAMES.iter().map(|n| n.to_string()))
+                .filter(|n| n.to_ascii_lowercase().starts_with(&prefix));
+            Some((offset, unique(names)))
+        }
+        Some((name, value)) => {
+            let name = name.trim();
+            // Comma separated headers complete the directive after the last comma.
+            let token_start = value.rfind(',').map_or(0, |i| i + 1);
+            let token = &value[token_start..];
+            let token = token.trim_start();
+            let start = line.len() - token.len();
+            let prefix = token.to_ascii_lowercase();
+
+            let values = history
+                .iter()
+                .filter(|h| h.name.eq_ignore_ascii_case(name))
+                .map(|h| h.value.clone())
+                .filter(|v| !v.is_empty())
+                .chain(header_values(name).iter().map(|v| v.to_string()))
+                .filter(|v| v.to_ascii_lowercase().starts_with(&prefix));
+            Some((start, unique(values)))
+        }
+    }
+}
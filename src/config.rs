@@ -0,0 +1,23 @@
+use crate::clipboard::ClipboardBackend;
+use crate::persistence::get_base_dir;
+use serde::{Deserialize, Serialize};
+use std::fs::read;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub clipboard: ClipboardBackend,
+    // Response bodies larger than this many bytes are written to disk.
+    pub response_limit: Option<usize>,
+}
+
+pub fn load_config() -> Result<Config, Box<dyn std::error::Error>> {
+    let mut path = get_base_dir()?;
+    path.push("config.json");
+    if !path.exists() {
+        return Ok(Config::default());
+    }
+    let contents = read(path)?;
+    let config: Config = serde_json::from_slice(&contents)?;
+    Ok(config)
+}
@@ -0,0 +1,212 @@
+use crate::sigv4::hex;
+use base64::Engine as _;
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, utf8_percent_encode};
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use std::collections::VecDeque;
+
+pub const NAMES: [&str; 9] = [
+    "$uuid",
+    "$timestamp",
+    "$isoTimestamp",
+    "$randomInt",
+    "$randomEmail",
+    "$base64",
+    "$urlencode",
+    "$sha256",
+    "$env",
+];
+
+// Unreserved characters (RFC 3986) are left as they are.
+const COMPONENT: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'_').remove(b'.').remove(b'~');
+
+// Replaces `{{name}}` placeholders with what `lookup` returns, leaving unknown ones as written.
+// Placeholders nest, so `{{$base64 {{user}}:{{password}}}}` encodes the substituted text.
+pub fn substitute(text: &str, lookup: &mut dyn FnMut(&str) -> Option<String>) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = closing(&rest[start + 2..]) else {
+            break;
+        };
+        let inner = substitute(&rest[start + 2..start + 2 + len], lookup);
+        result.push_str(&rest[..start]);
+        match lookup(inner.trim()) {
+            Some(value) => result.push_str(&value),
+            None => result.push_str(&format!("{{{{{}}}}}", inner)),
+        }
+        rest = &rest[start + 2 + len + 2..];
+    }
+
+    result.push_str(rest);
+    result
+}
+
+// The offset of the `}}` closing a placeholder whose contents start `text`.
+fn closing(text: &str) -> Option<usize> {
+    let bytes = text.as_bytes();
+    let mut depth = 0;
+    let mut i = 0;
+    while i + 1 < bytes.len() {
+        match &bytes[i..i + 2] {
+            b"{{" => {
+                depth += 1;
+                i += 2;
+            }
+            b"}}" if depth == 0 => return Some(i),
+            b"}}" => {
+                depth -= 1;
+                i += 2;
+            }
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+fn uuid() -> String {
+    let mut bytes: [u8; 16] = rand::random();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex = hex(&bytes);
+    format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+}
+
+// Evaluates a dynamic variable such as `$uuid` or `$randomInt 1 100`; None for unknown names
+// or bad arguments, which are then left as written.
+pub fn evaluate(expression: &str) -> Option<String> {
+    let (name, arg) = expression.split_once(char::is_whitespace).unwrap_or((expression, ""));
+    let arg = arg.trim();
+    let now = chrono::Utc::now();
+    let value = match (name, arg) {
+        ("$uuid", "") => uuid(),
+        ("$timestamp", "") => now.timestamp().to_string(),
+        ("$isoTimestamp", "") => now.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+        ("$randomInt", args) => {
+            let bounds: Vec<i64> = args.split_whitespace().map(str::parse).collect::<Result<_, _>>().ok()?;
+            let (min, max) = match bounds.as_slice() {
+                [] => (0, 1000),
+                [min, max] if min <= max => (*min, *max),
+                _ => return None,
+            };
+            rand::thread_rng().gen_range(min..=max).to_string()
+        }
+        ("$randomEmail", "") => {
+            let mut rng = rand::thread_rng();
+            let user: String = (0..10).map(|_| rng.gen_range('a'..='z')).collect();
+            format!("{}@example.com", user)
+        }
+        ("$base64", text) => base64::engine::general_purpose::STANDARD.encode(text),
+        ("$urlencode", text) => utf8_percent_encode(text, COMPONENT).to_string(),
+        ("$sha256", text) => hex(&Sha256::digest(text.as_bytes())),
+        ("$env", variable) if !variable.is_empty() => std::env::var(variable).ok()?,
+        _ => return None,
+    };
+    Some(value)
+}
+
+// The dynamic values of the last resolved request, in the order they were expanded. Values
+// pinned by `:resolve` are reused by the next send, so it sends what was shown.
+#[derive(Debug, Clone, Default)]
+pub struct Values {
+    pinned: VecDeque<(String, String)>,
+    pub expanded: Vec<(String, String)>,
+}
+
+impl Values {
+    pub fn start(&mut self) {
+        self.expanded.clear();
+    }
+
+    pub fn pin(&mut self) {
+        self.pinned = self.expanded.iter().cloned().collect();
+    }
+
+    pub fn unpin(&mut self) {
+        self.pinned.clear();
+    }
+
+    pub fn pinned(&self) -> bool {
+        !self.pinned.is_empty()
+    }
+
+    pub fn next(&mut self, expression: &str) -> Option<String> {
+        let value = match self.pinned.front() {
+            Some((pinned, _)) if pinned == expression => self.pinned.pop_front().map(|(_, v)| v)?,
+            _ => {
+                // The request changed since it was pinned; stop reusing values.
+                self.pinned.clear();
+                evaluate(expression)?
+            }
+        };
+        self.expanded.push((expression.to_string(), value.clone()));
+        Some(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "user" => Some("ada".to_string()),
+            "password" => Some("s3cret".to_string()),
+            name if name.starts_with('$') => evaluate(name),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn nested_placeholders_are_substituted_first() {
+        assert_eq!(
+            substitute("Basic {{$base64 {{user}}:{{password}}}}", &mut lookup),
+            "Basic YWRhOnMzY3JldA=="
+        );
+        assert_eq!(substitute("{{ user }}/{{$urlencode a b&c}}", &mut lookup), "ada/a%20b%26c");
+    }
+
+    #[test]
+    fn unknown_placeholders_are_left_as_written() {
+        assert_eq!(substitute("{{missing}} {{user}}", &mut lookup), "{{missing}} ada");
+        assert_eq!(substitute("{{$nope {{user}}}}", &mut lookup), "{{$nope ada}}");
+        assert_eq!(substitute("{{$randomInt 5 1}}", &mut lookup), "{{$randomInt 5 1}}");
+        assert_eq!(substitute("{{user", &mut lookup), "{{user");
+        assert_eq!(substitute("a }} b", &mut lookup), "a }} b");
+    }
+
+    #[test]
+    fn evaluates_dynamic_variables() {
+        let uuid = evaluate("$uuid").unwrap();
+        assert_eq!(uuid.len(), 36);
+        assert_eq!(&uuid[14..15], "4");
+        let n: i64 = evaluate("$randomInt 3 3").unwrap().parse().unwrap();
+        assert_eq!(n, 3);
+        assert_eq!(
+            evaluate("$sha256 abc").unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert!(evaluate("$uuid extra").is_none());
+        assert!(evaluate("$env").is_none());
+    }
+
+    #[test]
+    fn pinned_values_are_reused_until_the_request_changes() {
+        let mut values = Values::default();
+        values.start();
+        let first = values.next("$uuid").unwrap();
+        values.pin();
+        assert!(values.pinned());
+
+        values.start();
+        assert_eq!(values.next("$uuid").unwrap(), first);
+        assert!(!values.pinned());
+
+        values.pin();
+        values.start();
+        assert!(values.next("$timestamp").is_some());
+        assert!(!values.pinned());
+        assert_ne!(values.next("$uuid").unwrap(), first);
+    }
+}
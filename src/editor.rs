@@ -0,0 +1,53 @@
+use std::fs::{read_to_string, remove_file, write};
+use std::io;
+use std::process::Command;
+
+pub fn extension_for_content_type(content_type: &str) -> &'static str {
+    let media_type = content_type
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase();
+
+    match media_type.as_str() {
+        t if t == "application/json" || t.ends_with("+json") => "json",
+        t if t == "application/xml" || t == "text/xml" || t.ends_with("+xml") => "xml",
+        "text/html" => "html",
+        "application/yaml" | "application/x-yaml" | "text/yaml" => "yaml",
+        "application/graphql" => "graphql",
+        "text/csv" => "csv",
+        "application/javascript" | "text/javascript" => "js",
+        _ => "txt",
+    }
+}
+
+pub fn edit_text(text: &str, name: &str, extension: &str) -> io::Result<String> {
+    let mut path = std::env::temp_dir();
+    path.push(format!("vreq-{}-{}.{}", std::process::id(), name, extension));
+    write(&path, text)?;
+
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    // Allow values like "code --wait" by splitting off the arguments.
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().unwrap_or("vi");
+
+    let status = Command::new(program)
+        .args(parts)
+        .arg(&path)
+        .status()
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", program, e)));
+    let edited = status.and_then(|status| {
+        if status.success() {
+            read_to_string(&path)
+        } else {
+            Err(io::Error::other(format!("{} exited with {}", program, status)))
+        }
+    });
+    let _ = remove_file(&path);
+
+    // Editors append a final newline that the panels don't expect.
+    edited.map(|text| text.strip_suffix('\n').unwrap_or(&text).to_string())
+}
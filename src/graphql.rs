@@ -0,0 +1,299 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+
+pub const INTROSPECTION_QUERY: &str = "query IntrospectionQuery {
+  __schema {
+    queryType { name }
+    mutationType { name }
+    subscriptionType { name }
+    types {
+      name
+      fields(includeDeprecated: true) { name type { ...TypeRef } }
+    }
+  }
+}
+
+fragment TypeRef on __Type {
+  name
+  ofType { name ofType { name ofType { name ofType { name } } } }
+}";
+
+// The name of the first named operation, sent as `operationName`.
+pub fn operation_name(query: &str) -> Option<String> {
+    let tokens = tokenize(query);
+    tokens.windows(2).find_map(|pair| match pair {
+        [Token::Name(keyword), Token::Name(name)]
+            if matches!(keyword.as_str(), "query" | "mutation" | "subscription") =>
+        {
+            Some(name.clone())
+        }
+        _ => None,
+    })
+}
+
+pub fn request_body(query: &str, variables: &str) -> Result<Value, String> {
+    let variables: Value = if variables.trim().is_empty() {
+        Value::Null
+    } else {
+        serde_json::from_str(variables).map_err(|e| format!("invalid GraphQL variables: {}", e))?
+    };
+    let mut body = json!({ "query": query, "variables": variables });
+    if let Some(name) = operation_name(query) {
+        body["operationName"] = Value::String(name);
+    }
+    Ok(body)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Name(String),
+    Punct(char),
+}
+
+// Names and punctuation only; strings, comments and numbers don't matter for completion.
+fn tokenize(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '#' => {
+                while chars.next_if(|&c| c != '\n').is_some() {}
+            }
+            '"' => {
+                let mut escaped = false;
+                for c in chars.by_ref() {
+                    match c {
+                        '\\' if !escaped => escaped = true,
+                        '"' if !escaped => break,
+                        _ => escaped = false,
+                    }
+                }
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut name = c.to_string();
+                while let Some(c) = chars.next_if(|c| c.is_alphanumeric() || *c == '_') {
+                    name.push(c);
+                }
+                tokens.push(Token::Name(name));
+            }
+            '{' | '}' | '(' | ')' | ':' | '@' | '$' => tokens.push(Token::Punct(c)),
+            _ => {}
+        }
+    }
+    tokens
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Schema {
+    pub query_type: Option<String>,
+    pub mutation_type: Option<String>,
+    pub subscription_type: Option<String>,
+    // Field name to the name of its (unwrapped) type, per object or interface type.
+    pub types: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+fn named_type(type_ref: &Value) -> Option<String> {
+    match type_ref.get("name").and_then(Value::as_str) {
+        Some(name) => Some(name.to_string()),
+        None => named_type(type_ref.get("ofType")?),
+    }
+}
+
+impl Schema {
+    pub fn from_introspection(response: &Value) -> Result<Schema, String> {
+        if let Some(errors) = response.get("errors") {
+            return Err(format!("introspection failed: {}", errors));
+        }
+        let schema = response
+            .pointer("/data/__schema")
+            .ok_or("response has no data.__schema")?;
+        let root = |key: &str| {
+            schema
+                .pointer(&format!("/{}/name", key))
+                .and_then(Value::as_str)
+                .map(str::to_string)
+        };
+
+        let mut types = BTreeMap::new();
+        for ty in schema.get("types").and_then(Value::as_array).into_iter().flatten() {
+            let (Some(name), Some(fields)) = (
+                ty.get("name").and_then(Value::as_str),
+                ty.get("fields").and_then(Value::as_array),
+            ) else {
+                continue;
+            };
+            let fields = fields
+                .iter()
+                .filter_map(|f| {
+                    let field = f.get("name").and_then(Value::as_str)?;
+                    Some((field.to_string(), named_type(f.get("type")?)?))
+                })
+                .collect();
+            types.insert(name.to_string(), fields);
+        }
+
+        Ok(Schema {
+            query_type: root("queryType"),
+            mutation_type: root("mutationType"),
+            subscription_type: root("subscriptionType"),
+            types,
+        })
+    }
+
+    // Works out which type's selection set the end of `text` is in.
+    fn type_at_end(&self, text: &str) -> Option<String> {
+        let tokens = tokenize(text);
+        let mut stack: Vec<Option<String>> = Vec::new();
+        let mut parens = 0;
+        // The type the next `{` opens: set by an operation keyword, a type
+        // condition, or the last field name seen.
+        let mut next: Option<String> = None;
+        let mut prev: Option<&Token> = None;
+
+        for (i, token) in tokens.iter().enumerate() {
+            if parens > 0 {
+                match token {
+                    Token::Punct('(') => parens += 1,
+                    Token::Punct(')') => parens -= 1,
+                    _ => {}
+                }
+                prev = Some(token);
+                continue;
+            }
+            match token {
+                Token::Punct('(') => parens += 1,
+                Token::Punct('{') => {
+                    let ty = if stack.is_empty() && next.is_none() {
+                        self.query_type.clone()
+                    } else {
+                        next.take()
+                    };
+                    stack.push(ty);
+                }
+                Token::Punct('}') => {
+                    stack.pop();
+                    next = None;
+                }
+                Token::Name(name) if stack.is_empty() => match name.as_str() {
+                    "query" => next = self.query_type.clone(),
+                    "mutation" => next = self.mutation_type.clone(),
+                    "subscription" => next = self.subscription_type.clone(),
+                    _ if prev == Some(&Token::Name("on".to_string())) => next = Some(name.clone()),
+                    _ => {}
+                },
+                Token::Name(name) => {
+                    let after_on = prev == Some(&Token::Name("on".to_string()));
+                    let is_alias = tokens.get(i + 1) == Some(&Token::Punct(':'));
+                    if after_on {
+                        next = Some(name.clone());
+                    } else if !is_alias && prev != Some(&Token::Punct('@')) {
+                        let parent = stack.last().cloned().flatten();
+                        next = parent
+                            .and_then(|p| self.types.get(&p))
+                            .and_then(|fields| fields.get(name))
+                            .cloned();
+                    }
+                }
+                _ => {}
+            }
+            prev = Some(token);
+        }
+        stack.pop().flatten()
+    }
+
+    // Field names for the identifier being typed at the end of `text`.
+    pub fn complete(&self, text: &str) -> Option<(String, Vec<String>)> {
+        let prefix_len = text
+            .chars()
+            .rev()
+            .take_while(|c| c.is_alphanumeric() || *c == '_')
+            .map(char::len_utf8)
+            .sum::<usize>();
+        let (before, prefix) = text.split_at(text.len() - prefix_len);
+        let ty = self.type_at_end(before)?;
+        let fields = self.types.get(&ty)?;
+        let candidates = fields
+            .keys()
+            .filter(|f| f.starts_with(prefix))
+            .cloned()
+            .collect();
+        Some((prefix.to_string(), candidates))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schema() -> Schema {
+        let response = json!({ "data": { "__schema": {
+            "queryType": { "name": "Query" },
+            "mutationType": { "name": "Mutation" },
+            "subscriptionType": null,
+            "types": [
+                { "name": "Query", "fields": [
+                    { "name": "user", "type": { "name": "User" } },
+                    { "name": "users", "type": {
+                        "name": null,
+                        "ofType": { "name": null, "ofType": { "name": "User" } },
+                    } },
+                ] },
+                { "name": "Mutation", "fields": [
+                    { "name": "createUser", "type": { "name": "User" } },
+                ] },
+                { "name": "User", "fields": [
+                    { "name": "id", "type": { "name": "ID" } },
+                    { "name": "name", "type": { "name": "String" } },
+                    { "name": "friends", "type": { "name": null, "ofType": { "name": "User" } } },
+                ] },
+                { "name": "String", "fields": null },
+            ],
+        } } });
+        Schema::from_introspection(&response).unwrap()
+    }
+
+    fn complete(text: &str) -> Option<(String, Vec<String>)> {
+        schema().complete(text)
+    }
+
+    #[test]
+    fn request_body_names_the_operation() {
+        let query = "# query Ignored\nquery GetUser($id: ID) { user }";
+        let body = request_body(query, r#"{"id": 1}"#).unwrap();
+        assert_eq!(body["operationName"], "GetUser");
+        assert_eq!(body["variables"], json!({ "id": 1 }));
+
+        let body = request_body("{ user }", " ").unwrap();
+        assert!(body.get("operationName").is_none());
+        assert_eq!(body["variables"], Value::Null);
+
+        assert!(request_body("{ user }", "{").is_err());
+    }
+
+    #[test]
+    fn introspection_unwraps_list_and_non_null_types() {
+        let schema = schema();
+        assert_eq!(schema.query_type.as_deref(), Some("Query"));
+        assert_eq!(schema.subscription_type, None);
+        assert_eq!(schema.types["Query"]["users"], "User");
+        assert!(!schema.types.contains_key("String"));
+        assert!(Schema::from_introspection(&json!({ "errors": [] })).is_err());
+    }
+
+    #[test]
+    fn completes_fields_of_the_enclosing_selection() {
+        let names = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(complete("{ us"), Some(("us".to_string(), names(&["user", "users"]))));
+        assert_eq!(
+            complete("query Q($id: ID) { user(id: $id) { friends { n"),
+            Some(("n".to_string(), names(&["name"])))
+        );
+        assert_eq!(
+            complete("{ users { id } me: user { "),
+            Some((String::new(), names(&["friends", "id", "name"])))
+        );
+        assert_eq!(complete("mutation { c"), Some(("c".to_string(), names(&["createUser"]))));
+        assert_eq!(complete("{ user { name { "), None);
+    }
+}
@@ -0,0 +1,307 @@
+use crate::auth::{ApiKeyLocation, Auth};
+use crate::http::{Request, Response};
+use crate::stream::{Stream, StreamMessage};
+use http_body_util::BodyExt;
+use prost_reflect::prost::Message;
+use prost_reflect::{DescriptorPool, DynamicMessage, MethodDescriptor, SerializeOptions};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+
+const STATUS_NAMES: [&str; 17] = [
+    "OK",
+    "CANCELLED",
+    "UNKNOWN",
+    "INVALID_ARGUMENT",
+    "DEADLINE_EXCEEDED",
+    "NOT_FOUND",
+    "ALREADY_EXISTS",
+    "PERMISSION_DENIED",
+    "RESOURCE_EXHAUSTED",
+    "FAILED_PRECONDITION",
+    "ABORTED",
+    "OUT_OF_RANGE",
+    "UNIMPLEMENTED",
+    "INTERNAL",
+    "UNAVAILABLE",
+    "DATA_LOSS",
+    "UNAUTHENTICATED",
+];
+
+pub fn is_grpc_url(url: &str) -> bool {
+    let url = url.trim_start().to_ascii_lowercase();
+    url.starts_with("grpc://") || url.starts_with("grpcs://")
+}
+
+// A descriptor set is read as is; `.proto` files are compiled with protoc,
+// with the file's directory as the import path.
+pub fn load_descriptors(path: &str) -> Result<DescriptorPool, Box<dyn std::error::Error>> {
+    let path = std::path::Path::new(path);
+    let bytes = if path.extension().is_some_and(|ext| ext == "proto") {
+        let out = std::env::temp_dir().join(format!("vreq-{}.pb", std::process::id()));
+        let include = path.parent().filter(|p| !p.as_os_str().is_empty());
+        let output = Command::new("protoc")
+            .arg("--include_imports")
+            .arg(format!("--descriptor_set_out={}", out.display()))
+            .arg(format!("-I{}", include.map_or(".".into(), |p| p.display().to_string())))
+            .arg(path)
+            .output()
+            .map_err(|e| format!("cannot run protoc: {}", e))?;
+        if !output.status.success() {
+            return Err(format!("protoc: {}", String::from_utf8_lossy(&output.stderr).trim()).into());
+        }
+        let bytes = std::fs::read(&out)?;
+        let _ = std::fs::remove_file(&out);
+        bytes
+    } else {
+        std::fs::read(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?
+    };
+    Ok(DescriptorPool::decode(bytes.as_slice())?)
+}
+
+// Every method as `package.Service/Method`, the path it is called at.
+pub fn methods(pool: &DescriptorPool) -> Vec<String> {
+    pool.services()
+        .flat_map(|service| {
+            service
+                .methods()
+                .map(|method| format!("{}/{}", service.full_name(), method.name()))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+fn find_method(pool: &DescriptorPool, path: &str) -> Result<MethodDescriptor, String> {
+    let (service, method) = path
+        .trim_matches('/')
+        .split_once('/')
+        .ok_or_else(|| format!("expected /package.Service/Method, got /{}", path))?;
+    pool.get_service_by_name(service)
+        .ok_or_else(|| format!("unknown service: {}", service))?
+        .methods()
+        .find(|m| m.name() == method)
+        .ok_or_else(|| format!("unknown method: {}/{}", service, method))
+}
+
+// The method's input message with every field at its default, as a starting point for the Body.
+pub fn message_template(pool: &DescriptorPool, path: &str) -> Option<String> {
+    let method = find_method(pool, path).ok()?;
+    let message = DynamicMessage::new(method.input());
+    let options = SerializeOptions::new().skip_default_fields(false);
+    let value = message
+        .serialize_with_options(serde_json::value::Serializer, &options)
+        .ok()?;
+    serde_json::to_string_pretty(&value).ok()
+}
+
+// `grpc://host:port/package.Service/Method` is called over cleartext HTTP/2, `grpcs://` over TLS.
+fn endpoint(url: &str) -> Result<(String, String), Box<dyn std::error::Error>> {
+    let url = reqwest::Url::parse(url.trim())?;
+    let scheme = if url.scheme() == "grpcs" { "https" } else { "http" };
+    let host = url.host_str().ok_or("URL has no host")?;
+    let authority = match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    };
+    Ok((format!("{}://{}", scheme, authority), url.path().to_string()))
+}
+
+// Splits complete length-prefixed messages off the front of `buf`.
+fn take_message(buf: &mut Vec<u8>) -> Result<Option<Vec<u8>>, String> {
+    if buf.len() < 5 {
+        return Ok(None);
+    }
+    if buf[0] != 0 {
+        return Err("compressed gRPC messages are not supported".to_string());
+    }
+    let len = u32::from_be_bytes([buf[1], buf[2], buf[3], buf[4]]) as usize;
+    if buf.len() < 5 + len {
+        return Ok(None);
+    }
+    let message = buf[5..5 + len].to_vec();
+    buf.drain(..5 + len);
+    Ok(Some(message))
+}
+
+fn status_error(code: &str, message: &str) -> String {
+    let name = code
+        .parse::<usize>()
+        .ok()
+        .and_then(|c| STATUS_NAMES.get(c))
+        .unwrap_or(&"UNKNOWN");
+    let message = percent_encoding::percent_decode_str(message).decode_utf8_lossy();
+    if message.is_empty() {
+        format!("grpc-status {} {}", code, name)
+    } else {
+        format!("grpc-status {} {}: {}", code, name, message)
+    }
+}
+
+// Encodes the Body up front so mistakes in it are reported before anything is sent.
+pub fn call(request: Request, pool: &DescriptorPool) -> Result<Stream, Box<dyn std::error::Error>> {
+    let (base, path) = endpoint(&request.url)?;
+    let method = find_method(pool, &path)?;
+    if method.is_client_streaming() {
+        return Err("client and bidirectional streaming methods are not supported".into());
+    }
+    let body = if request.body.trim().is_empty() { "{}" } else { request.body.as_str() };
+    let mut deserializer = serde_json::Deserializer::from_str(body);
+    let message = DynamicMessage::deserialize(method.input(), &mut deserializer)
+        .map_err(|e| format!("invalid {} message: {}", method.input().full_name(), e))?;
+    deserializer.end()?;
+
+    let encoded = message.encode_to_vec();
+    let mut frame = vec![0];
+    frame.extend_from_slice(&(encoded.len() as u32).to_be_bytes());
+    frame.extend_from_slice(&encoded);
+
+    let url = format!("{}{}", base, path);
+    Ok(Stream::spawn(move |sender, stop| {
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+        runtime.block_on(run(&request, &url, frame, &method, sender, stop))
+    }))
+}
+
+async fn run(
+    request: &Request,
+    url: &str,
+    frame: Vec<u8>,
+    method: &MethodDescriptor,
+    sender: &Sender<StreamMessage>,
+    stop: &AtomicBool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let client = reqwest::Client::builder().http2_prior_knowledge().build()?;
+    let mut builder = client
+        .post(url)
+        .header("content-type", "application/grpc")
+        .header("te", "trailers");
+    for header in request.headers.iter().filter(|h| h.enabled) {
+        builder = builder.header(&header.name, &header.value);
+    }
+    builder = match &request.auth {
+        Auth::None => builder,
+        Auth::Basic { username, password } => builder.basic_auth(username, Some(password)),
+        Auth::Bearer { token } => builder.bearer_auth(token),
+        Auth::ApiKey { name, value, location: ApiKeyLocation::Header } => builder.header(name, value),
+        auth => return Err(format!("{} auth is not supported for gRPC", auth.kind()).into()),
+    };
+
+    let resp = builder.body(frame).send().await?;
+    let head = Response {
+        status: resp.status().as_u16(),
+        status_text: resp.status().to_string(),
+        headers: resp
+            .headers()
+            .iter()
+            .map(|(k, v)| (k.to_string(), String::from_utf8_lossy(v.as_bytes()).to_string()))
+            .collect(),
+        ..Response::default()
+    };
+    if !resp.status().is_success() {
+        return Err(format!("HTTP {}", head.status_text).into());
+    }
+    // Errors without a body put the status in the headers ("trailers-only").
+    let mut status = head
+        .header("grpc-status")
+        .map(|code| (code.to_string(), head.header("grpc-message").unwrap_or("").to_string()));
+    sender.send(StreamMessage::Started(head))?;
+
+    let mut body = reqwest::Body::from(resp);
+    let mut buf = Vec::new();
+    while let Some(frame) = body.frame().await {
+        if stop.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        let frame = match frame?.into_data() {
+            Ok(data) => {
+                buf.extend_from_slice(&data);
+                while let Some(bytes) = take_message(&mut buf)? {
+                    let message = DynamicMessage::decode(method.output(), bytes.as_slice())?;
+                    let json = serde_json::to_string_pretty(&message)?;
+                    sender.send(StreamMessage::Text(format!("{}\n", json)))?;
+                }
+                continue;
+            }
+            Err(frame) => frame,
+        };
+        if let Ok(trailers) = frame.into_trailers() {
+            let value = |name: &str| {
+                trailers
+                    .get(name)
+                    .map(|v| String::from_utf8_lossy(v.as_bytes()).to_string())
+            };
+            if let Some(code) = value("grpc-status") {
+                status = Some((code, value("grpc-message").unwrap_or_default()));
+            }
+        }
+    }
+
+    match status {
+        Some((code, _)) if code == "0" => Ok(()),
+        Some((code, message)) => Err(status_error(&code, &message).into()),
+        None => Err("response ended without a grpc-status".into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(message: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0];
+        frame.extend_from_slice(&(message.len() as u32).to_be_bytes());
+        frame.extend_from_slice(message);
+        frame
+    }
+
+    #[test]
+    fn messages_split_across_chunks_wait_for_the_rest() {
+        let frame = frame(b"hello");
+        let mut buf = frame[..3].to_vec();
+        assert_eq!(take_message(&mut buf), Ok(None));
+        buf.extend_from_slice(&frame[3..7]);
+        assert_eq!(take_message(&mut buf), Ok(None));
+        buf.extend_from_slice(&frame[7..]);
+        assert_eq!(take_message(&mut buf), Ok(Some(b"hello".to_vec())));
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn one_chunk_can_hold_several_messages() {
+        let mut buf = frame(b"one");
+        buf.extend(frame(b""));
+        buf.extend(frame(b"three"));
+        buf.extend_from_slice(&[0, 0]);
+        assert_eq!(take_message(&mut buf), Ok(Some(b"one".to_vec())));
+        assert_eq!(take_message(&mut buf), Ok(Some(Vec::new())));
+        assert_eq!(take_message(&mut buf), Ok(Some(b"three".to_vec())));
+        assert_eq!(take_message(&mut buf), Ok(None));
+        assert_eq!(buf, [0, 0]);
+    }
+
+    #[test]
+    fn compressed_messages_are_rejected() {
+        let mut buf = frame(b"zipped");
+        buf[0] = 1;
+        assert!(take_message(&mut buf).is_err());
+    }
+
+    #[test]
+    fn status_errors_name_the_code() {
+        assert_eq!(status_error("5", ""), "grpc-status 5 NOT_FOUND");
+        assert_eq!(
+            status_error("3", "name%20is%20required%3A%20%E2%9C%97"),
+            "grpc-status 3 INVALID_ARGUMENT: name is required: ✗"
+        );
+        assert_eq!(status_error("99", "x"), "grpc-status 99 UNKNOWN: x");
+    }
+
+    #[test]
+    fn endpoint_maps_the_scheme() {
+        let (base, path) = endpoint("grpcs://api.test/pkg.Service/Method").unwrap();
+        assert_eq!((base.as_str(), path.as_str()), ("https://api.test", "/pkg.Service/Method"));
+        let (base, _) = endpoint(" grpc://localhost:50051/pkg.Service/Method").unwrap();
+        assert_eq!(base, "http://localhost:50051");
+        assert!(endpoint("grpc:///pkg.Service/Method").is_err());
+    }
+}
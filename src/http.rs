@@ -1,6 +1,26 @@
-use serde::{Deserialize, Serialize};
+use crate::assertions::Assertion;
+use crate::capture::Capture;
+use crate::auth::{self, Auth};
+use crate::graphql;
+use crate::persistence::response_file_path;
+use crate::sigv4;
+use crate::timing::{Recorder, Timing};
+use encoding_rs::{Encoding, UTF_8};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Instant;
+use crate::query::{self, QueryParam};
+use serde::{Deserialize, Deserializer, Serialize};
 use std::collections::HashMap;
 
+// Bodies larger than the limit are streamed to a file and only this much is kept for display.
+const PREVIEW_BYTES: usize = 64 * 1024;
+pub const DEFAULT_RESPONSE_LIMIT: usize = 10 * 1024 * 1024;
+
+// Variant names double as the serialized form in saved collections.
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
 pub enum Method {
     #[default]
@@ -11,6 +31,16 @@ pub enum Method {
     PATCH,
 }
 
+impl Method {
+    pub const ALL: [Method; 5] = [
+        Method::GET,
+        Method::POST,
+        Method::PUT,
+        Method::DELETE,
+        Method::PATCH,
+    ];
+}
+
 impl std::fmt::Display for Method {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -36,12 +66,163 @@ impl From<String> for Method {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BodyType {
+    #[default]
+    Raw,
+    Json,
+    Form,
+    Multipart,
+    Binary,
+    GraphQL,
+}
+
+impl BodyType {
+    pub const ALL: [BodyType; 6] = [
+        BodyType::Raw,
+        BodyType::Json,
+        BodyType::Form,
+        BodyType::Multipart,
+        BodyType::Binary,
+        BodyType::GraphQL,
+    ];
+}
+
+impl std::fmt::Display for BodyType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BodyType::Raw => write!(f, "raw"),
+            BodyType::Json => write!(f, "json"),
+            BodyType::Form => write!(f, "form"),
+            BodyType::Multipart => write!(f, "multipart"),
+            BodyType::Binary => write!(f, "binary"),
+            BodyType::GraphQL => write!(f, "graphql"),
+        }
+    }
+}
+
+impl std::str::FromStr for BodyType {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "raw" | "text" => Ok(BodyType::Raw),
+            "json" => Ok(BodyType::Json),
+            "form" | "urlencoded" | "x-www-form-urlencoded" => Ok(BodyType::Form),
+            "multipart" | "form-data" | "multipart/form-data" => Ok(BodyType::Multipart),
+            "binary" | "file" => Ok(BodyType::Binary),
+            "graphql" | "gql" => Ok(BodyType::GraphQL),
+            _ => Err(format!("unknown body type: {}", value)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Header {
+    pub name: String,
+    pub value: String,
+    #[serde(default = "header_enabled_default", skip_serializing_if = "is_enabled")]
+    pub enabled: bool,
+}
+
+fn header_enabled_default() -> bool {
+    true
+}
+
+fn is_enabled(enabled: &bool) -> bool {
+    *enabled
+}
+
+impl Header {
+    pub fn new(name: String, value: String) -> Self {
+        Self { name, value, enabled: true }
+    }
+}
+
+pub fn parse_headers(text: &str) -> Vec<Header> {
+    text.lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            let (enabled, line) = match line.strip_prefix('#') {
+                Some(rest) => (false, rest.trim_start()),
+                None => (true, line),
+            };
+            let (name, value) = line.split_once(':')?;
+            Some(Header {
+                name: name.trim().to_string(),
+                value: value.trim().to_string(),
+                enabled,
+            })
+        })
+        .collect()
+}
+
+pub fn format_headers(headers: &[Header]) -> String {
+    headers
+        .iter()
+        .map(|h| {
+            if h.enabled {
+                format!("{}: {}", h.name, h.value)
+            } else {
+                format!("# {}: {}", h.name, h.value)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Collections saved before headers were ordered store them as a JSON object.
+fn deserialize_headers<'de, D>(deserializer: D) -> Result<Vec<Header>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Headers {
+        List(Vec<Header>),
+        Map(HashMap<String, String>),
+    }
+
+    Ok(match Headers::deserialize(deserializer)? {
+        Headers::List(headers) => headers,
+        Headers::Map(map) => {
+            let mut headers: Vec<Header> =
+                map.into_iter().map(|(k, v)| Header::new(k, v)).collect();
+            headers.sort_by(|a, b| a.name.cmp(&b.name));
+            headers
+        }
+    })
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Request {
     pub method: Method,
     pub url: String,
-    pub headers: HashMap<String, String>,
+    #[serde(deserialize_with = "deserialize_headers")]
+    pub headers: Vec<Header>,
     pub body: String,
+    #[serde(default)]
+    pub body_type: BodyType,
+    #[serde(default, skip_serializing_if = "Auth::is_none")]
+    pub auth: Auth,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub disabled_params: Vec<QueryParam>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub variables: String,
+    // Descriptor set or .proto file describing grpc:// requests.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub proto: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub assertions: Vec<Assertion>,
+    // Variables set from the response after a successful send.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub captures: Vec<Capture>,
+    // Rhai scripts run before sending and after the response arrives.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub pre_script: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub post_script: String,
 }
 
 impl Default for Request {
@@ -49,8 +230,17 @@ impl Default for Request {
         Self {
             method: Method::GET,
             url: String::new(),
-            headers: HashMap::new(),
+            headers: Vec::new(),
             body: String::new(),
+            body_type: BodyType::Raw,
+            auth: Auth::None,
+            disabled_params: Vec::new(),
+            variables: String::new(),
+            proto: String::new(),
+            assertions: Vec::new(),
+            captures: Vec::new(),
+            pre_script: String::new(),
+            post_script: String::new(),
         }
     }
 }
@@ -60,13 +250,22 @@ impl Request {
         Self {
             method,
             url,
-            headers: HashMap::new(),
+            headers: Vec::new(),
             body: String::new(),
+            body_type: BodyType::Raw,
+            auth: Auth::None,
+            disabled_params: Vec::new(),
+            variables: String::new(),
+            proto: String::new(),
+            assertions: Vec::new(),
+            captures: Vec::new(),
+            pre_script: String::new(),
+            post_script: String::new(),
         }
     }
 
-    pub fn with_header(mut self, key: String, value: String) -> Self {
-        self.headers.insert(key, value);
+    pub fn with_header(mut self, header: Header) -> Self {
+        self.headers.push(header);
         self
     }
 
@@ -80,9 +279,139 @@ impl Request {
 pub struct Response {
     pub status: u16,
     pub status_text: String,
-    pub headers: HashMap<String, String>,
+    pub headers: Vec<(String, String)>,
     pub body: String,
+    // The raw body, or its first bytes when the whole body was written to `body_file`.
+    pub raw: Vec<u8>,
+    pub body_file: Option<PathBuf>,
+    pub binary: bool,
     pub duration_ms: u128,
+    pub timing: Timing,
+}
+
+impl Response {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn size(&self) -> usize {
+        self.timing.response_body_bytes
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        match &self.body_file {
+            Some(file) => std::fs::copy(file, path).map(|_| ()),
+            None => std::fs::write(path, &self.raw),
+        }?;
+        Ok(())
+    }
+
+    pub fn hex_dump(&self) -> String {
+        let shown = &self.raw[..self.raw.len().min(PREVIEW_BYTES)];
+        let mut lines: Vec<String> = shown
+            .chunks(16)
+            .enumerate()
+            .map(|(i, chunk)| {
+                let hex: Vec<String> = (0..16)
+                    .map(|j| chunk.get(j).map_or("  ".to_string(), |b| format!("{:02x}", b)))
+                    .collect();
+                let ascii: String = chunk
+                    .iter()
+                    .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+                    .collect();
+                format!("{:08x}  {}  {}  |{}|", i * 16, hex[..8].join(" "), hex[8..].join(" "), ascii)
+            })
+            .collect();
+        if self.size() > shown.len() {
+            lines.push(format!("... {} more bytes", self.size() - shown.len()));
+        }
+        lines.join("\n")
+    }
+}
+
+fn content_type_param<'a>(content_type: &'a str, name: &str) -> Option<&'a str> {
+    content_type.split(';').skip(1).find_map(|param| {
+        let (key, value) = param.split_once('=')?;
+        key.trim()
+            .eq_ignore_ascii_case(name)
+            .then(|| value.trim().trim_matches('"'))
+    })
+}
+
+fn is_text_type(mime: &str) -> bool {
+    let mime = mime.to_ascii_lowercase();
+    mime.starts_with("text/")
+        || mime.ends_with("+json")
+        || mime.ends_with("+xml")
+        || [
+            "application/json",
+            "application/xml",
+            "application/javascript",
+            "application/ecmascript",
+            "application/x-www-form-urlencoded",
+            "application/graphql",
+            "application/yaml",
+            "application/x-yaml",
+            "application/x-ndjson",
+            "image/svg+xml",
+        ]
+        .contains(&mime.as_str())
+}
+
+// Without a textual Content-Type the body is sniffed for NUL bytes and invalid UTF-8.
+pub fn is_binary(content_type: Option<&str>, content_encoding: Option<&str>, bytes: &[u8]) -> bool {
+    if content_encoding.is_some_and(|e| !e.eq_ignore_ascii_case("identity")) {
+        return true;
+    }
+    let mime = content_type.map(|ct| ct.split(';').next().unwrap_or("").trim());
+    match mime {
+        Some(mime) if is_text_type(mime) => false,
+        Some(mime) if !mime.is_empty() && mime != "application/octet-stream" => true,
+        _ => {
+            let sample = &bytes[..bytes.len().min(8192)];
+            let valid = match std::str::from_utf8(sample) {
+                Ok(_) => true,
+                // A multi-byte character cut off at the end of the sample is fine.
+                Err(e) => e.error_len().is_none(),
+            };
+            sample.contains(&0) || !valid
+        }
+    }
+}
+
+pub fn decode(bytes: &[u8], content_type: Option<&str>) -> String {
+    let encoding = content_type
+        .and_then(|ct| content_type_param(ct, "charset"))
+        .and_then(|charset| Encoding::for_label(charset.as_bytes()))
+        .unwrap_or(UTF_8);
+    let (text, _, _) = encoding.decode(bytes);
+    text.into_owned()
+}
+
+struct ReadBody {
+    raw: Vec<u8>,
+    size: usize,
+    file: Option<PathBuf>,
+}
+
+// Keeps up to `limit` bytes in memory; anything larger is written to a file.
+fn read_body(resp: &mut impl Read, limit: usize) -> Result<ReadBody, Box<dyn std::error::Error>> {
+    let mut raw = Vec::new();
+    resp.take(limit as u64 + 1).read_to_end(&mut raw)?;
+    if raw.len() <= limit {
+        let size = raw.len();
+        return Ok(ReadBody { raw, size, file: None });
+    }
+
+    let path = response_file_path()?;
+    let mut file = File::create(&path)?;
+    file.write_all(&raw)?;
+    let size = raw.len() + std::io::copy(resp, &mut file)? as usize;
+    raw.truncate(PREVIEW_BYTES);
+    Ok(ReadBody { raw, size, file: Some(path) })
 }
 
 impl Default for Response {
@@ -90,17 +419,103 @@ impl Default for Response {
         Response {
             status: u16::MAX,
             status_text: String::from("Invalid Request"),
-            headers: HashMap::new(),
+            headers: Vec::new(),
             body: String::from(""),
+            raw: Vec::new(),
+            body_file: None,
+            binary: false,
             duration_ms: 0,
+            timing: Timing::default(),
         }
     }
 }
 
-pub fn send_request(request: &Request) -> Result<Response, Box<dyn std::error::Error>> {
-    let client = reqwest::blocking::Client::new();
-    let start = std::time::Instant::now();
+impl Request {
+    pub fn has_header(&self, name: &str) -> bool {
+        self.headers
+            .iter()
+            .any(|h| h.enabled && h.name.eq_ignore_ascii_case(name))
+    }
 
+    pub fn form_fields(&self) -> Vec<(String, String)> {
+        query::parse_params(&self.body)
+            .into_iter()
+            .filter(|p| p.enabled)
+            .map(|p| (p.key, p.value.unwrap_or_default()))
+            .collect()
+    }
+}
+
+fn apply_body(
+    mut req_builder: reqwest::blocking::RequestBuilder,
+    request: &Request,
+) -> Result<reqwest::blocking::RequestBuilder, Box<dyn std::error::Error>> {
+    // Whitespace is a valid raw payload; other body types have nothing to send.
+    let empty = match request.body_type {
+        BodyType::Raw => request.body.is_empty(),
+        _ => request.body.trim().is_empty(),
+    };
+    if empty {
+        return Ok(req_builder);
+    }
+
+    match request.body_type {
+        BodyType::Raw => Ok(req_builder.body(request.body.clone())),
+        BodyType::Json => {
+            if !request.has_header("content-type") {
+                req_builder = req_builder.header("Content-Type", "application/json");
+            }
+            Ok(req_builder.body(request.body.clone()))
+        }
+        BodyType::Form => Ok(req_builder.form(&request.form_fields())),
+        BodyType::Multipart => {
+            let mut form = reqwest::blocking::multipart::Form::new();
+            for (key, value) in request.form_fields() {
+                form = match value.strip_prefix('@') {
+                    Some(path) => form.file(key, path.trim())?,
+                    None => form.text(key, value),
+                };
+            }
+            Ok(req_builder.multipart(form))
+        }
+        BodyType::GraphQL => {
+            let body = graphql::request_body(&request.body, &request.variables)?;
+            // GraphQL over HTTP puts GET operations in the query string.
+            if request.method == Method::GET {
+                let params: Vec<(&str, String)> = body
+                    .as_object()
+                    .into_iter()
+                    .flatten()
+                    .filter(|(_, value)| !value.is_null())
+                    .map(|(key, value)| match value {
+                        serde_json::Value::String(s) => (key.as_str(), s.clone()),
+                        value => (key.as_str(), value.to_string()),
+                    })
+                    .collect();
+                return Ok(req_builder.query(&params));
+            }
+            if !request.has_header("content-type") {
+                req_builder = req_builder.header("Content-Type", "application/json");
+            }
+            Ok(req_builder.body(body.to_string()))
+        }
+        BodyType::Binary => {
+            let path = request.body.trim();
+            let path = path.strip_prefix('@').unwrap_or(path);
+            let bytes = std::fs::read(path)
+                .map_err(|e| format!("cannot read {}: {}", path, e))?;
+            if !request.has_header("content-type") {
+                req_builder = req_builder.header("Content-Type", "application/octet-stream");
+            }
+            Ok(req_builder.body(bytes))
+        }
+    }
+}
+
+fn build_request(
+    client: &reqwest::blocking::Client,
+    request: &Request,
+) -> Result<reqwest::blocking::RequestBuilder, Box<dyn std::error::Error>> {
     let mut req_builder = match request.method {
         Method::GET => client.get(&request.url),
         Method::POST => client.post(&request.url),
@@ -109,29 +524,339 @@ pub fn send_request(request: &Request) -> Result<Response, Box<dyn std::error::E
         Method::PATCH => client.patch(&request.url),
     };
 
-    let mut headers = HashMap::new();
+    // Form and multipart bodies bring their own Content-Type, including the boundary.
+    let generated = matches!(request.body_type, BodyType::Form | BodyType::Multipart);
+    for header in request.headers.iter().filter(|h| h.enabled) {
+        if generated && header.name.eq_ignore_ascii_case("content-type") {
+            continue;
+        }
+        req_builder = req_builder.header(&header.name, &header.value);
+    }
+
+    req_builder = request.auth.apply(req_builder);
+    apply_body(req_builder, request)
+}
+
+// Approximates the bytes written for the request line, headers and body.
+fn request_size(req: &reqwest::blocking::Request) -> usize {
+    let url = req.url();
+    let target = match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    };
+    let body = req.body().and_then(|b| b.as_bytes()).map_or(0, <[u8]>::len);
+    let mut size = format!("{} {} HTTP/1.1\r\n", req.method(), target).len() + 2 + body;
+    for (name, value) in req.headers() {
+        size += name.as_str().len() + value.len() + 4;
+    }
+    if !req.headers().contains_key(reqwest::header::HOST) {
+        size += "host: \r\n".len() + url.host_str().unwrap_or("").len();
+    }
+    if body > 0 && !req.headers().contains_key(reqwest::header::CONTENT_LENGTH) {
+        size += "content-length: \r\n".len() + body.to_string().len();
+    }
+    size
+}
+
+fn sign(req: &mut reqwest::blocking::Request, auth: &Auth) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some((credentials, region, service)) = auth.aws_signing() {
+        if credentials.access_key.is_empty() || credentials.secret_key.is_empty() {
+            return Err("aws credentials are not set".into());
+        }
+        if region.is_empty() || service.is_empty() || service.contains("{{") {
+            return Err("aws region and service must be set".into());
+        }
+        sigv4::sign_request(req, &credentials, &region, &service)?;
+    }
+    Ok(())
+}
+
+// The request as send_request writes it, without sending it. The headers hyper and reqwest
+// add on the way out (Host, Accept, Content-Length) are filled in as they would be.
+pub fn preview(request: &Request) -> Result<String, Box<dyn std::error::Error>> {
+    let client = reqwest::blocking::Client::new();
+    let mut req = build_request(&client, request)?.build()?;
+    sign(&mut req, &request.auth)?;
+
+    let url = req.url();
+    let target = match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    };
+    let mut text = format!("{} {} HTTP/1.1\n", req.method(), target);
+    let headers = req.headers();
+    if !headers.contains_key(reqwest::header::HOST) {
+        let host = url.host_str().unwrap_or("");
+        match url.port() {
+            Some(port) => text.push_str(&format!("host: {}:{}\n", host, port)),
+            None => text.push_str(&format!("host: {}\n", host)),
+        }
+    }
+    for (name, value) in headers {
+        text.push_str(&format!("{}: {}\n", name, String::from_utf8_lossy(value.as_bytes())));
+    }
+    if !headers.contains_key(reqwest::header::ACCEPT) {
+        text.push_str("accept: */*\n");
+    }
+    let body = match req.body().map(|b| b.as_bytes()) {
+        None => String::new(),
+        Some(None) => "(streamed from the multipart files when sent)".to_string(),
+        Some(Some(bytes)) => {
+            if !headers.contains_key(reqwest::header::CONTENT_LENGTH) {
+                text.push_str(&format!("content-length: {}\n", bytes.len()));
+            }
+            match std::str::from_utf8(bytes) {
+                Ok(body) => body.to_string(),
+                Err(_) => format!("(binary body, {} bytes)", bytes.len()),
+            }
+        }
+    };
+    text.push('\n');
+    text.push_str(&body);
+    Ok(text)
+}
+
+// Header lines that parse_headers drops, with their line numbers.
+pub fn header_warnings(text: &str) -> Vec<String> {
+    text.lines()
+        .enumerate()
+        .filter_map(|(i, line)| {
+            let line = line.trim();
+            let header = line.strip_prefix('#').unwrap_or(line).trim_start();
+            match header.split_once(':') {
+                _ if header.is_empty() => None,
+                None => Some(format!("Headers line {} has no ':' and is ignored: {}", i + 1, line)),
+                Some((name, _)) if name.trim().is_empty() => {
+                    Some(format!("Headers line {} has no header name: {}", i + 1, line))
+                }
+                Some(_) => None,
+            }
+        })
+        .collect()
+}
+
+// reqwest's errors keep the useful part ("relative URL without a base") in their sources.
+pub fn describe_error(err: &dyn std::error::Error) -> String {
+    let mut text = err.to_string();
+    let mut source = err.source();
+    while let Some(err) = source {
+        text.push_str(&format!(": {}", err));
+        source = err.source();
+    }
+    text
+}
+
+fn execute(
+    client: &reqwest::blocking::Client,
+    req_builder: reqwest::blocking::RequestBuilder,
+    auth: &Auth,
+    recorder: &Recorder,
+) -> Result<(reqwest::blocking::Response, usize), Box<dyn std::error::Error>> {
+    let mut req = req_builder.build()?;
+    sign(&mut req, auth)?;
+    let size = request_size(&req);
+    recorder.start();
+    Ok((client.execute(req)?, size))
+}
+
+// Sends the request, answering a Digest challenge with a second request if needed.
+fn exchange(
+    client: &reqwest::blocking::Client,
+    request: &Request,
+    recorder: &Recorder,
+) -> Result<(reqwest::blocking::Response, usize), Box<dyn std::error::Error>> {
+    let (resp, request_bytes) =
+        execute(client, build_request(client, request)?, &request.auth, recorder)?;
 
-    for (key, value) in &request.headers {
-        req_builder = req_builder.header(key, value);
-        headers.insert(key.to_string(), value.to_string());
+    if let Auth::Digest { username, password } = &request.auth
+        && resp.status() == reqwest::StatusCode::UNAUTHORIZED
+    {
+        let challenge = resp
+            .headers()
+            .get_all(reqwest::header::WWW_AUTHENTICATE)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .find(|v| v.trim_start().to_ascii_lowercase().starts_with("digest"))
+            .map(str::to_string);
+        let url = resp.url();
+        let uri = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        };
+        let method = request.method.to_string();
+
+        if let Some(authorization) = challenge.and_then(|challenge| {
+            auth::digest_authorization(&challenge, username, password, &method, &uri)
+        }) {
+            let req_builder = build_request(client, request)?
+                .header(reqwest::header::AUTHORIZATION, authorization);
+            return execute(client, req_builder, &request.auth, recorder);
+        }
     }
+    Ok((resp, request_bytes))
+}
 
-    if !request.body.is_empty() {
-        req_builder = req_builder.body(request.body.clone());
+fn response_head(resp: &reqwest::blocking::Response) -> Response {
+    Response {
+        status: resp.status().as_u16(),
+        status_text: resp.status().to_string(),
+        headers: resp
+            .headers()
+            .iter()
+            .map(|(k, v)| (k.to_string(), String::from_utf8_lossy(v.as_bytes()).to_string()))
+            .collect(),
+        ..Response::default()
     }
+}
 
-    let resp = req_builder.send()?;
+// Returns the status and headers along with the unread body, without the default timeout.
+pub fn open_stream(
+    request: &Request,
+) -> Result<(Response, reqwest::blocking::Response), Box<dyn std::error::Error>> {
+    let client = reqwest::blocking::Client::builder().timeout(None).build()?;
+    let (resp, _) = exchange(&client, request, &Recorder::default())?;
+    Ok((response_head(&resp), resp))
+}
+
+pub fn send_request(request: &Request, limit: usize) -> Result<Response, Box<dyn std::error::Error>> {
+    let recorder = Recorder::new(&request.url);
+    let client = reqwest::blocking::Client::builder()
+        .dns_resolver(Arc::new(recorder.clone()))
+        .connector_layer(recorder.clone())
+        .build()?;
+    let start = Instant::now();
+
+    let (mut resp, request_bytes) = exchange(&client, request, &recorder)?;
+    let headers_at = Instant::now();
+
+    let mut response = response_head(&resp);
+    let header_bytes = format!("{:?} {}\r\n", resp.version(), resp.status()).len()
+        + response.headers.iter().map(|(k, v)| k.len() + v.len() + 4).sum::<usize>()
+        + 2;
+    let body = read_body(&mut resp, limit)?;
     let duration = start.elapsed().as_millis();
 
-    let status = resp.status().as_u16();
-    let status_text = resp.status().to_string();
-    let body = resp.text()?;
+    let timing = Timing {
+        request_bytes,
+        response_header_bytes: header_bytes,
+        response_body_bytes: body.size,
+        ..recorder.finish(headers_at, Instant::now())
+    };
 
-    Ok(Response {
-        status,
-        status_text,
-        headers,
-        body,
-        duration_ms: duration,
-    })
+    response.duration_ms = duration;
+    response.timing = timing;
+    response.binary = is_binary(
+        response.header("content-type"),
+        response.header("content-encoding"),
+        &body.raw,
+    );
+    if !response.binary {
+        response.body = decode(&body.raw, response.header("content-type"));
+    }
+    response.raw = body.raw;
+    response.body_file = body.file;
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn built_body(request: &Request) -> Option<Vec<u8>> {
+        let client = reqwest::blocking::Client::new();
+        let builder = apply_body(client.post("http://localhost/"), request).unwrap();
+        let built = builder.build().unwrap();
+        built.body().and_then(|body| body.as_bytes()).map(|bytes| bytes.to_vec())
+    }
+
+    #[test]
+    fn whitespace_raw_body_is_sent() {
+        let request = Request::new(Method::POST, String::new()).with_body(" \n".to_string());
+        assert_eq!(built_body(&request), Some(b" \n".to_vec()));
+    }
+
+    #[test]
+    fn empty_bodies_are_skipped() {
+        let request = Request::new(Method::POST, String::new());
+        assert_eq!(built_body(&request), None);
+
+        let mut request = request.with_body("  ".to_string());
+        request.body_type = BodyType::Json;
+        assert_eq!(built_body(&request), None);
+    }
+
+    #[test]
+    fn binary_bodies_are_sniffed() {
+        assert!(!is_binary(None, None, "plain text, café".as_bytes()));
+        assert!(is_binary(None, None, b"ab\0cd"));
+        assert!(is_binary(Some("application/octet-stream"), None, b"\xff\xfe"));
+        // "é" is two bytes; the sample ending halfway through it is still text.
+        let mut body = vec![b'a'; 8191];
+        body.extend_from_slice("é".as_bytes());
+        assert!(!is_binary(None, None, &body));
+        // Inside the sample a broken character makes it binary.
+        body.truncate(8190);
+        body.extend_from_slice(b"\xc3a");
+        assert!(is_binary(None, None, &body));
+        assert!(!is_binary(Some("application/json; charset=utf-8"), None, b"\0"));
+        assert!(is_binary(Some("image/png"), None, b"png"));
+        assert!(is_binary(Some("text/plain"), Some("gzip"), b"text"));
+    }
+
+    #[test]
+    fn charset_is_decoded() {
+        let latin1 = b"caf\xe9";
+        assert_eq!(decode(latin1, Some("text/plain; charset=iso-8859-1")), "café");
+        assert_eq!(decode(latin1, Some("text/plain; charset=\"ISO-8859-1\"")), "café");
+        assert_eq!(decode("café".as_bytes(), Some("text/plain")), "café");
+        assert_eq!(decode(latin1, None), "caf\u{fffd}");
+    }
+
+    #[test]
+    fn bodies_over_the_limit_spill_to_a_file() {
+        let body = b"0123456789";
+        let read = read_body(&mut &body[..], body.len()).unwrap();
+        assert_eq!((read.raw.as_slice(), read.size, read.file), (&body[..], 10, None));
+
+        let read = read_body(&mut &body[..], body.len() - 1).unwrap();
+        let file = read.file.unwrap();
+        let written = std::fs::read(&file).unwrap();
+        let _ = std::fs::remove_file(&file);
+        assert_eq!(read.size, 10);
+        assert_eq!(read.raw, body);
+        assert_eq!(written, body);
+    }
+
+    #[test]
+    fn hex_dump_pads_the_last_line() {
+        let mut response = Response { raw: b"0123456789abcdefGHI\n".to_vec(), ..Response::default() };
+        response.timing.response_body_bytes = response.raw.len();
+        assert_eq!(
+            response.hex_dump(),
+            "00000000  30 31 32 33 34 35 36 37  38 39 61 62 63 64 65 66  |0123456789abcdef|\n\
+             00000010  47 48 49 0a                                       |GHI.|"
+        );
+        response.timing.response_body_bytes = 100;
+        assert!(response.hex_dump().ends_with("\n... 80 more bytes"));
+    }
+
+    #[test]
+    fn generated_content_type_replaces_the_typed_one() {
+        let client = reqwest::blocking::Client::new();
+        for (body_type, expected) in [
+            (BodyType::Form, "application/x-www-form-urlencoded"),
+            (BodyType::Multipart, "multipart/form-data; boundary="),
+        ] {
+            let header = Header::new("Content-Type".to_string(), "application/json".to_string());
+            let mut request = Request::new(Method::POST, "http://localhost/".to_string())
+                .with_header(header)
+                .with_body("a = 1".to_string());
+            request.body_type = body_type;
+            let built = build_request(&client, &request).unwrap().build().unwrap();
+            let values: Vec<_> =
+                built.headers().get_all(reqwest::header::CONTENT_TYPE).iter().collect();
+            assert_eq!(values.len(), 1, "{:?}", values);
+            assert!(values[0].to_str().unwrap().starts_with(expected), "{:?}", values);
+        }
+    }
 }
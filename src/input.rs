@@ -13,6 +13,7 @@ pub fn handle_key_event(app: &mut App, key: KeyEvent) -> bool {
         Mode::Command => modes::command::handle_key(app, key),
         Mode::Visual => modes::visual::handle_key(app, key),
     }
+    app.sync_query_params();
 
     !app.should_quit
 }
@@ -2,11 +2,32 @@
 mod macros;
 
 mod app;
+mod assertions;
+mod auth;
+mod buffer;
+mod capture;
+mod cli;
+mod clipboard;
+mod completion;
+mod config;
+mod dynamic;
+mod editor;
+mod graphql;
+mod grpc;
 mod http;
 mod input;
 mod modes;
+mod oauth;
 mod persistence;
+mod query;
+mod runner;
+mod script;
+mod sigv4;
+mod stream;
+mod timing;
 mod ui;
+mod util;
+mod websocket;
 
 use app::{App, Mode};
 use crossterm::{
@@ -21,18 +42,35 @@ use ratatui::{
 };
 use std::io;
 
-use crate::persistence::list_collections;
+use crate::config::load_config;
+use crate::persistence::{
+    list_collections, list_environments, load_command_history, load_header_history, load_history,
+    load_tokens,
+};
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(code) = cli::main(&args) {
+        std::process::exit(code);
+    }
+
     enable_raw_mode()?;
     let mut stdout = io::stdout();
     execute!(stdout, EnterAlternateScreen, EnableMouseCapture, Show)?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
     let collections = list_collections()?;
+    let config = load_config()?;
 
     let mut app = App::new();
     app.set_collections(collections);
+    app.config = config;
+    app.environments = list_environments()?;
+    app.command_history = load_command_history()?;
+    app.header_history = load_header_history()?;
+    app.history = load_history()?;
+    app.tokens = load_tokens()?;
+    app.interactive = true;
     let res = run_app(&mut terminal, &mut app);
 
     disable_raw_mode()?;
@@ -63,6 +101,10 @@ fn run_app<B: ratatui::backend::Backend>(
         };
         execute!(io::stdout(), cursor_style)?;
 
+        app.poll_stream();
+        app.poll_run();
+        app.poll_authorization();
+        app.poll_websocket();
         terminal.draw(|f| ui::render(f, app))?;
 
         if event::poll(std::time::Duration::from_millis(100))? {
@@ -73,5 +115,29 @@ fn run_app<B: ratatui::backend::Backend>(
                 _ => (),
             }
         }
+
+        if app.should_open_editor {
+            app.should_open_editor = false;
+            open_editor(terminal, app)?;
+        }
+    }
+}
+
+fn open_editor<B: ratatui::backend::Backend>(
+    terminal: &mut Terminal<B>,
+    app: &mut App,
+) -> io::Result<()> {
+    disable_raw_mode()?;
+    execute!(io::stdout(), LeaveAlternateScreen, DisableMouseCapture)?;
+
+    let res = app.edit_current_buffer();
+
+    enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen, EnableMouseCapture)?;
+    terminal.clear()?;
+
+    if let Err(err) = res {
+        app.error(format!("E: editor: {}", err));
     }
+    Ok(())
 }
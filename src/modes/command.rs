@@ -1,30 +1,228 @@
-use crate::app::{App, Mode, Panel};
-use crate::http::{Method, Request, send_request};
-use crate::persistence::{Collection, save_collection, load_collection};
-use crossterm::event::{KeyCode, KeyEvent};
+use crate::app::{App, Completion, Mode, Panel, PendingCommand};
+use crate::assertions::Assertion;
+use crate::auth::Auth;
+use crate::capture::Capture;
+use crate::script::Hook;
+use crate::oauth::{Grant, OAuth2Config};
+use crate::buffer;
+use crate::http::{BodyType, Method};
+use crate::runner;
+use crate::websocket::Outgoing;
+use crate::persistence::{
+    Collection, append_command_history, is_secret_command, list_environments, load_collection,
+    load_environment, save_collection,
+};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+const COMMANDS: [&str; 26] = [
+    "assert", "auth", "body", "capture", "clear", "e", "edit", "env", "gql", "load", "method",
+    "oauth", "preview", "proto", "q", "quit", "resolve", "run", "save", "saveresp", "script",
+    "send", "stop", "stream", "test", "ws",
+];
 
 pub fn handle_key(app: &mut App, key: KeyEvent) {
+    if app.pending_command == Some(PendingCommand::Register) {
+        app.clear_pending_command();
+        if let KeyCode::Char(c) = key.code {
+            app.selected_register = Some(c);
+            if let Some(text) = app.register_contents() {
+                insert_str(app, &text.replace('\n', " "));
+            }
+        }
+        return;
+    }
+
+    if key.code != KeyCode::Tab && key.code != KeyCode::BackTab {
+        app.completion = None;
+    }
+    if !matches!(key.code, KeyCode::Up | KeyCode::Down) {
+        app.history_index = None;
+    }
+
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
     match key.code {
         KeyCode::Esc => {
             app.set_mode(Mode::Normal);
         }
 
         KeyCode::Enter => {
-            let _ = execute_command(app);
+            let cmd = app.command_buffer.trim().to_string();
+            if !cmd.is_empty() && !is_secret_command(&cmd) && app.command_history.last() != Some(&cmd) {
+                let _ = append_command_history(&cmd);
+                app.command_history.push(cmd);
+            }
+            if let Err(err) = execute_command(app) {
+                app.error(format!("E: {}", err));
+            }
             app.set_mode(Mode::Normal);
         }
 
+        KeyCode::Char('r') if ctrl => {
+            app.set_pending_command(PendingCommand::Register);
+        }
+        KeyCode::Char('a') if ctrl => {
+            app.command_cursor = 0;
+        }
+        KeyCode::Char('e') if ctrl => {
+            app.command_cursor = app.command_buffer.len();
+        }
+        KeyCode::Char('u') if ctrl => {
+            let cursor = app.command_cursor;
+            app.command_buffer.replace_range(..cursor, "");
+            app.command_cursor = 0;
+        }
+        KeyCode::Char('w') if ctrl => {
+            let cursor = app.command_cursor;
+            let start = buffer::word_backward(&app.command_buffer, cursor);
+            app.command_buffer.replace_range(start..cursor, "");
+            app.command_cursor = start;
+        }
         KeyCode::Char(c) => {
-            app.command_buffer.push(c);
+            let cursor = app.command_cursor;
+            app.command_cursor = buffer::insert_char(&mut app.command_buffer, cursor, c);
         }
         KeyCode::Backspace => {
-            app.command_buffer.pop();
+            if app.command_buffer.is_empty() {
+                app.set_mode(Mode::Normal);
+            } else if app.command_cursor > 0 {
+                let cursor = app.command_cursor;
+                app.command_cursor = buffer::delete_before(&mut app.command_buffer, cursor);
+            }
+        }
+        KeyCode::Delete => {
+            let cursor = app.command_cursor;
+            if cursor < app.command_buffer.len() {
+                buffer::delete_at(&mut app.command_buffer, cursor);
+            }
         }
 
+        KeyCode::Left => {
+            app.command_cursor = buffer::prev_boundary(&app.command_buffer, app.command_cursor);
+        }
+        KeyCode::Right => {
+            app.command_cursor = buffer::next_boundary(&app.command_buffer, app.command_cursor);
+        }
+        KeyCode::Home => {
+            app.command_cursor = 0;
+        }
+        KeyCode::End => {
+            app.command_cursor = app.command_buffer.len();
+        }
+
+        KeyCode::Up => recall_history(app, true),
+        KeyCode::Down => recall_history(app, false),
+
+        KeyCode::Tab => complete(app, true),
+        KeyCode::BackTab => complete(app, false),
+
         _ => {}
     }
 }
 
+fn insert_str(app: &mut App, text: &str) {
+    let cursor = app.command_cursor;
+    app.command_buffer.insert_str(cursor, text);
+    app.command_cursor = cursor + text.len();
+}
+
+fn recall_history(app: &mut App, older: bool) {
+    if app.history_index.is_none() {
+        app.history_prefix = app.command_buffer.clone();
+    }
+
+    let prefix = app.history_prefix.clone();
+    let matches = |i: &usize| app.command_history[*i].starts_with(&prefix);
+    let next = match (app.history_index, older) {
+        (None, true) => (0..app.command_history.len()).rev().find(matches),
+        (None, false) => None,
+        (Some(index), true) => (0..index).rev().find(matches).or(Some(index)),
+        (Some(index), false) => (index + 1..app.command_history.len()).find(matches),
+    };
+
+    match next {
+        Some(index) => {
+            app.history_index = Some(index);
+            app.command_buffer = app.command_history[index].clone();
+        }
+        None if app.history_index.is_some() => {
+            app.history_index = None;
+            app.command_buffer = prefix;
+        }
+        None => {}
+    }
+    app.command_cursor = app.command_buffer.len();
+}
+
+fn complete(app: &mut App, forward: bool) {
+    if let Some(completion) = app.completion.as_mut() {
+        let count = completion.candidates.len();
+        completion.index = if forward {
+            (completion.index + 1) % count
+        } else {
+            (completion.index + count - 1) % count
+        };
+    } else {
+        let line = &app.command_buffer[..app.command_cursor];
+        let start = line.rfind(' ').map_or(0, |i| i + 1);
+        let word = &line[start..];
+        let candidates: Vec<String> = completion_candidates(app, &line[..start])
+            .into_iter()
+            .filter(|c| c.starts_with(word))
+            .collect();
+        if candidates.is_empty() {
+            return;
+        }
+        let index = if forward { 0 } else { candidates.len() - 1 };
+        let prefix = word.to_string();
+        app.completion = Some(Completion { candidates, index, start, prefix });
+    }
+
+    if let Some(completion) = &app.completion {
+        let candidate = &completion.candidates[completion.index];
+        app.command_buffer.truncate(completion.start);
+        app.command_buffer.push_str(candidate);
+        app.command_cursor = app.command_buffer.len();
+    }
+}
+
+fn completion_candidates(app: &App, before: &str) -> Vec<String> {
+    let words: Vec<&str> = before.split_whitespace().collect();
+
+    match words.as_slice() {
+        [] => COMMANDS.iter().map(|c| c.to_string()).collect(),
+        ["method"] => Method::ALL.iter().map(|m| m.to_string()).collect(),
+        ["body"] => BodyType::ALL.iter().map(|b| b.to_string()).collect(),
+        ["auth"] => Auth::KINDS.iter().map(|k| k.to_string()).collect(),
+        ["auth", "oauth2"] | ["oauth", "grant"] => {
+            Grant::ALL.iter().map(|g| g.to_string()).collect()
+        }
+        ["oauth"] => ["token", "clear", "grant"]
+            .into_iter()
+            .chain(OAuth2Config::FIELDS)
+            .map(str::to_string)
+            .collect(),
+        ["assert"] => ["status", "header", "json", "time", "schema", "clear", "rm"]
+            .into_iter()
+            .map(str::to_string)
+            .collect(),
+        ["capture", _, "="] => ["json", "regex", "header", "cookie"]
+            .into_iter()
+            .map(str::to_string)
+            .collect(),
+        ["capture"] => vec!["clear".to_string(), "rm".to_string()],
+        ["script"] => Hook::ALL.iter().map(|h| h.name().to_string()).collect(),
+        ["load" | "save" | "test" | "run"] => app
+            .collections
+            .iter()
+            .map(|c| c.strip_suffix(".json").unwrap_or(c).to_string())
+            .collect(),
+        ["env"] => app.environments.clone(),
+        ["ws"] => vec!["ping".to_string(), "close".to_string()],
+        ["gql"] => vec!["schema".to_string()],
+        _ => Vec::new(),
+    }
+}
+
 fn execute_command(app: &mut App) -> Result<(), Box<dyn std::error::Error>>{
     let cmd = app.command_buffer.trim();
 
@@ -36,53 +234,269 @@ fn execute_command(app: &mut App) -> Result<(), Box<dyn std::error::Error>>{
         _ if cmd.starts_with("method ") => {
             let method_split: Vec<&str> = cmd.split(' ').collect();
             app.current_request.method = Method::from(method_split[1].to_uppercase());
+            app.info(format!("method {}", app.current_request.method));
             Ok(())
         }
+        "resolve" => app.pin_dynamic_values(),
+        "preview" => app.preview_request(),
         "send" => {
-            let mut req = Request::new(app.current_request.method, app.url_buffer.clone());
-            for (key, value) in app.parsed_headers() {
-                req.headers.insert(key, value);
+            app.send_current_request();
+            Ok(())
+        }
+        "stream" => {
+            app.stream_current_request();
+            Ok(())
+        }
+        "stop" => {
+            if !app.stop_stream() && !app.stop_run() && !app.cancel_authorization() {
+                return Err("no stream, run or authorization is in progress".into());
             }
-            req.body = app.body_buffer.clone();
-            app.current_request = req;
-            let response = send_request(&app.current_request).unwrap_or_default();
-            app.last_response = Some(response);
-            app.update_response_buffer();
-            app.set_panel(Panel::Response);
-
+            Ok(())
+        }
+        _ if cmd.starts_with("body ") => {
+            let body_type: BodyType = cmd["body ".len()..].trim().parse()?;
+            app.current_request.body_type = body_type;
+            if body_type != BodyType::GraphQL && app.active_panel == Panel::Variables {
+                app.set_panel(Panel::Body);
+            }
+            app.info(format!("body {}", body_type));
+            Ok(())
+        }
+        "auth" => {
+            let kind = app.current_request.auth.kind();
+            app.info(format!("auth {}", kind));
+            Ok(())
+        }
+        _ if cmd.starts_with("auth ") => {
+            let mut auth = Auth::parse(cmd["auth ".len()..].trim())?;
+            // Switching grants keeps the rest of an existing OAuth2 setup.
+            if let (Auth::OAuth2(new), Auth::OAuth2(current)) = (&mut auth, &app.current_request.auth) {
+                *new = OAuth2Config { grant: new.grant, ..current.clone() };
+            }
+            app.info(format!("auth {}", auth.kind()));
+            app.current_request.auth = auth;
+            Ok(())
+        }
+        _ if cmd == "oauth" || cmd.starts_with("oauth ") => {
+            let cmd = cmd.to_string();
+            let Auth::OAuth2(config) = app.current_request.auth.clone() else {
+                return Err("auth is not oauth2 (use :auth oauth2 <grant>)".into());
+            };
+            let mut args = cmd["oauth".len()..].trim().splitn(2, ' ');
+            match (args.next().unwrap_or(""), args.next()) {
+                ("token", None) => {
+                    let resolved = config.map_values(|v| app.substitute(v));
+                    app.oauth_token(&resolved, true)?;
+                    app.info("oauth: token acquired");
+                }
+                ("clear", None) => {
+                    app.clear_oauth_token(&config)?;
+                    app.info("oauth: token cleared");
+                }
+                (field, Some(value)) => {
+                    let mut config = config;
+                    config.set(field, value.trim())?;
+                    app.current_request.auth = Auth::OAuth2(config);
+                    app.info(format!("oauth {} set", field));
+                }
+                _ => return Err("usage: :oauth <field> <value> | token | clear".into()),
+            }
+            Ok(())
+        }
+        _ if cmd == "ws" || cmd.starts_with("ws ") => {
+            let mut args = cmd["ws".len()..].trim().splitn(2, ' ');
+            let message = match (args.next().unwrap_or(""), args.next()) {
+                ("ping", payload) => Outgoing::Ping(payload.unwrap_or("").as_bytes().to_vec()),
+                ("close", args) => {
+                    let mut args = args.unwrap_or("").trim().splitn(2, ' ');
+                    let code = match args.next().filter(|c| !c.is_empty()) {
+                        Some(code) => code.parse().map_err(|_| format!("invalid close code: {}", code))?,
+                        None => 1000,
+                    };
+                    Outgoing::Close(code, args.next().unwrap_or("").to_string())
+                }
+                _ => return Err("usage: :ws ping [payload] | close [code] [reason]".into()),
+            };
+            app.websocket_send(message)
+        }
+        "gql schema" => app.fetch_schema(),
+        "assert" => {
+            let assertions = &app.current_request.assertions;
+            let text = if assertions.is_empty() {
+                "no assertions".to_string()
+            } else {
+                assertions
+                    .iter()
+                    .enumerate()
+                    .map(|(i, a)| format!("{}: {}", i + 1, a))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            app.info(text);
+            Ok(())
+        }
+        "assert clear" => {
+            app.current_request.assertions.clear();
+            app.info("assertions cleared");
+            Ok(())
+        }
+        _ if cmd.starts_with("assert rm ") => {
+            let index = cmd["assert rm ".len()..].trim();
+            let count = app.current_request.assertions.len();
+            match index.parse::<usize>() {
+                Ok(n) if (1..=count).contains(&n) => {
+                    let removed = app.current_request.assertions.remove(n - 1);
+                    app.info(format!("removed {}", removed));
+                    Ok(())
+                }
+                _ => Err(format!("no assertion {} ({} defined)", index, count).into()),
+            }
+        }
+        _ if cmd.starts_with("assert ") => {
+            let assertion: Assertion = cmd["assert ".len()..].parse()?;
+            app.info(format!("assert {}", assertion));
+            app.current_request.assertions.push(assertion);
+            Ok(())
+        }
+        "capture" => {
+            let captures = &app.current_request.captures;
+            let text = if captures.is_empty() {
+                "no captures".to_string()
+            } else {
+                captures
+                    .iter()
+                    .enumerate()
+                    .map(|(i, c)| format!("{}: {}", i + 1, c))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            app.info(text);
+            Ok(())
+        }
+        "capture clear" => {
+            app.current_request.captures.clear();
+            app.info("captures cleared");
+            Ok(())
+        }
+        _ if cmd.starts_with("capture rm ") => {
+            let index = cmd["capture rm ".len()..].trim();
+            let count = app.current_request.captures.len();
+            match index.parse::<usize>() {
+                Ok(n) if (1..=count).contains(&n) => {
+                    let removed = app.current_request.captures.remove(n - 1);
+                    app.info(format!("removed {}", removed));
+                    Ok(())
+                }
+                _ => Err(format!("no capture {} ({} defined)", index, count).into()),
+            }
+        }
+        _ if cmd.starts_with("capture ") => {
+            let capture: Capture = cmd["capture ".len()..].parse()?;
+            app.info(format!("capture {}", capture));
+            app.current_request.captures.push(capture);
+            Ok(())
+        }
+        "script" => {
+            let request = &app.current_request;
+            let set = |script: &str| if script.trim().is_empty() { "none" } else { "set" };
+            let text = format!("pre {}, post {}", set(&request.pre_script), set(&request.post_script));
+            app.info(text);
+            Ok(())
+        }
+        _ if cmd.starts_with("script ") => {
+            let hook: Hook = cmd["script ".len()..].trim().parse()?;
+            app.editing_script = Some(hook);
+            app.should_open_editor = true;
+            Ok(())
+        }
+        "test" => app.test_current_request(),
+        _ if cmd.starts_with("test ") => {
+            let name = cmd["test ".len()..].trim().to_string();
+            app.run_collection(&name, runner::Options::default())
+        }
+        _ if cmd.starts_with("run ") => {
+            let mut name = None;
+            let mut options = runner::Options::default();
+            let mut args = cmd["run ".len()..].split_whitespace();
+            while let Some(arg) = args.next() {
+                match arg {
+                    _ if options.take(arg, &mut args)? => {}
+                    flag if flag.starts_with('-') => {
+                        return Err(format!("unknown option: {}", flag).into());
+                    }
+                    _ if name.is_some() => {
+                        return Err(format!("unexpected argument: {}", arg).into());
+                    }
+                    _ => name = Some(arg.to_string()),
+                }
+            }
+            let name = name.ok_or(
+                "usage: run <collection> [--data <file>] [--iterations <n>] [--delay <ms>] [--stop-on-failure]",
+            )?;
+            app.run_collection(&name, options)
+        }
+        "proto" => {
+            match &app.descriptors {
+                Some((path, _)) => {
+                    let message = format!("{} ({} methods)", path, app.grpc_methods.len());
+                    app.info(message);
+                }
+                None => app.info("no descriptors loaded"),
+            }
+            Ok(())
+        }
+        _ if cmd.starts_with("proto ") => {
+            let path = cmd["proto ".len()..].trim().to_string();
+            let path = match path.strip_prefix("~/") {
+                Some(rest) => format!("{}/{}", std::env::var("HOME").unwrap_or_default(), rest),
+                None => path,
+            };
+            app.load_proto(&path)?;
+            app.info(format!("{} gRPC methods", app.grpc_methods.len()));
+            Ok(())
+        }
+        "e" | "edit" => {
+            app.should_open_editor = true;
             Ok(())
         }
         "clear" => {
-            app.last_response = None;
+            app.stop_stream();
+            app.set_last_response(None);
+            Ok(())
+        }
+        "env" => {
+            app.environments = list_environments()?;
+            app.show_environment();
+            Ok(())
+        }
+        _ if cmd.starts_with("env ") => {
+            let name = cmd["env ".len()..].trim();
+            app.active_environment = Some(load_environment(name)?);
+            app.info(format!("environment {}", name));
             app.update_response_buffer();
             Ok(())
         }
         _ if cmd.starts_with("load ") => {
-            let cmd_split: Vec<&str> = cmd.split(' ').collect();
-            let collection: Collection = load_collection(cmd_split[1])?;
-            let req = collection.saved_request.request;
-            app.url_buffer = req.url.clone();
-            app.headers_buffer = req.headers.iter()
-                .map(|(k, v)| format!("{}: {}", k, v))
-                .collect::<Vec<_>>()
-                .join("\n");
-            app.body_buffer = req.body.clone();
-            app.current_request = req;
+            let name = cmd["load ".len()..].trim().to_string();
+            let collection: Collection = load_collection(&name)?;
+            app.apply_request(collection.saved_request.request);
+            app.request_name = Some(name.clone());
+            app.info(format!("loaded {}", name));
             Ok(())
         }
+        _ if cmd.starts_with("saveresp ") => {
+            let path = cmd["saveresp ".len()..].trim().to_string();
+            app.save_response(&path)
+        }
         _ if cmd.starts_with("save ") => {
             let cmd_split: Vec<&str> = cmd.split(' ').collect();
-            app.current_request.body = app.body_buffer.clone();
-            app.current_request.url = app.url_buffer.clone();
-
-            for (key, value) in app.parsed_headers() {
-                app.current_request.headers.insert(key, value);
-            }
+            app.current_request = app.build_request();
             let collection = Collection::new(String::from(cmd_split[1]), app.current_request.clone());
-            save_collection(&collection)
-        }
-        _ => {
+            save_collection(&collection)?;
+            app.request_name = Some(collection.name.clone());
+            app.info(format!("saved {}", collection.name));
             Ok(())
         }
+        _ => Err(format!("unknown command: {}", cmd).into()),
     }
 }
@@ -1,8 +1,35 @@
-use crate::app::{App, Mode, Panel};
+use crate::app::{App, Completion, Mode, Panel};
+use crate::buffer;
+use crate::completion;
+use crate::http::BodyType;
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 
 pub fn handle_key(app: &mut App, key: KeyEvent) {
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+    if app.insert_completion.is_some() {
+        match key.code {
+            KeyCode::Char('n' | 'p') if ctrl => {}
+            KeyCode::Char('y') if ctrl => {
+                app.insert_completion = None;
+                return;
+            }
+            KeyCode::Enter => {
+                app.insert_completion = None;
+                return;
+            }
+            KeyCode::Char('e') if ctrl => {
+                if let Some(completion) = app.insert_completion.take() {
+                    replace_completed(app, completion.start, &completion.prefix);
+                }
+                return;
+            }
+            _ => app.insert_completion = None,
+        }
+    }
+
     match key.code {
+        KeyCode::Char('n') if ctrl => complete(app, true),
+        KeyCode::Char('p') if ctrl => complete(app, false),
         KeyCode::Esc => {
             app.set_mode(Mode::Normal);
             app.clear_pending_command();
@@ -13,38 +40,31 @@ pub fn handle_key(app: &mut App, key: KeyEvent) {
 
         KeyCode::Char(c) => {
             let cursor = app.cursor();
-            if let Some(buffer) = app.current_buffer_mut() {
-                buffer.insert(cursor, c);
-                app.set_cursor(cursor + 1);
+            if let Some(text) = app.current_buffer_mut() {
+                let new_cursor = buffer::insert_char(text, cursor, c);
+                app.set_cursor(new_cursor);
             }
         }
 
         KeyCode::Backspace => {
             let cursor = app.cursor();
-            if let Some(buffer) = app.current_buffer_mut() && cursor > 0 {
-                buffer.remove(cursor - 1);
-                app.set_cursor(cursor - 1);
+            if let Some(text) = app.current_buffer_mut() && cursor > 0 {
+                let new_cursor = buffer::delete_before(text, cursor);
+                app.set_cursor(new_cursor);
             }
         }
         KeyCode::Delete => {
             let cursor = app.cursor();
-            if let Some(buffer) = app.current_buffer_mut() && !buffer.is_empty() {
-                buffer.remove(cursor);
+            if let Some(text) = app.current_buffer_mut() && cursor < text.len() {
+                buffer::delete_at(text, cursor);
             }
         }
 
         KeyCode::Left => {
-            let cursor = app.cursor();
-            if cursor > 0 {
-                app.set_cursor(cursor - 1);
-            }
+            app.move_cursor_left();
         }
         KeyCode::Right => {
-            let cursor = app.cursor();
-            let buffer_len = app.current_buffer().len();
-            if cursor < buffer_len {
-                app.set_cursor(cursor + 1);
-            }
+            app.move_cursor_right();
         }
         KeyCode::Up => {
             app.move_cursor_up();
@@ -55,13 +75,55 @@ pub fn handle_key(app: &mut App, key: KeyEvent) {
 
         KeyCode::Enter => {
             let cursor = app.cursor();
-            if (app.active_panel == Panel::Headers || app.active_panel == Panel::Body)
-                && let Some(buffer) = app.current_buffer_mut()
+            if matches!(app.active_panel, Panel::Params | Panel::Headers | Panel::Body | Panel::Variables)
+                && let Some(text) = app.current_buffer_mut()
             {
-                buffer.insert(cursor, '\n');
-                app.set_cursor(cursor + 1);
+                let new_cursor = buffer::insert_char(text, cursor, '\n');
+                app.set_cursor(new_cursor);
             }
         }
         _ => {}
     }
 }
+
+fn replace_completed(app: &mut App, start: usize, text: &str) {
+    let cursor = app.cursor();
+    if let Some(buffer) = app.current_buffer_mut() {
+        buffer.replace_range(start..cursor, text);
+        app.set_cursor(start + text.len());
+    }
+}
+
+fn complete(app: &mut App, forward: bool) {
+    if let Some(completion) = app.insert_completion.as_mut() {
+        let count = completion.candidates.len();
+        completion.index = if forward {
+            (completion.index + 1) % count
+        } else {
+            (completion.index + count - 1) % count
+        };
+    } else {
+        if app.active_panel == Panel::Body && app.current_request.body_type == BodyType::GraphQL {
+            app.load_schema();
+        }
+        let cursor = app.cursor();
+        let buffer = app.current_buffer();
+        let line_start = buffer[..cursor].rfind('\n').map_or(0, |i| i + 1);
+        let Some((start, candidates)) = completion::candidates(app, &buffer[line_start..cursor])
+        else {
+            return;
+        };
+        if candidates.is_empty() {
+            return;
+        }
+        let start = line_start + start;
+        let prefix = buffer[start..cursor].to_string();
+        let index = if forward { 0 } else { candidates.len() - 1 };
+        app.insert_completion = Some(Completion { candidates, index, start, prefix });
+    }
+
+    if let Some(completion) = &app.insert_completion {
+        let candidate = completion.candidates[completion.index].clone();
+        replace_completed(app, completion.start, &candidate);
+    }
+}
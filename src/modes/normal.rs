@@ -1,11 +1,22 @@
 use crate::{
-    app::{App, Mode, Panel, PendingCommand},
-    http::{Request, send_request},
+    app::{App, Mode, Panel, PendingCommand, ResponseView},
+    http::BodyType,
     persistence::load_collection,
+    websocket::Outgoing,
 };
 use crossterm::event::{KeyCode, KeyEvent};
 
 pub fn handle_key(app: &mut App, key: KeyEvent) {
+    if app.pending_command == Some(PendingCommand::Register) {
+        if let KeyCode::Char(c) = key.code {
+            app.selected_register = Some(c);
+        }
+        app.clear_pending_command();
+        return;
+    }
+
+    // The Variables panel only exists for GraphQL bodies.
+    let graphql = app.current_request.body_type == BodyType::GraphQL;
     match key.code {
         KeyCode::Char('i') => {
             app.set_mode(Mode::Insert);
@@ -16,16 +27,21 @@ pub fn handle_key(app: &mut App, key: KeyEvent) {
 
         KeyCode::Tab => match app.active_panel {
             Panel::Sidebar => app.set_panel(Panel::Url),
-            Panel::Url => app.set_panel(Panel::Headers),
+            Panel::Url => app.set_panel(Panel::Params),
+            Panel::Params => app.set_panel(Panel::Headers),
             Panel::Headers => app.set_panel(Panel::Body),
-            Panel::Body => app.set_panel(Panel::Response),
+            Panel::Body if graphql => app.set_panel(Panel::Variables),
+            Panel::Body | Panel::Variables => app.set_panel(Panel::Response),
             Panel::Response => app.set_panel(Panel::Sidebar),
         },
         KeyCode::BackTab => match app.active_panel {
             Panel::Sidebar => app.set_panel(Panel::Response),
             Panel::Url => app.set_panel(Panel::Sidebar),
-            Panel::Headers => app.set_panel(Panel::Url),
+            Panel::Params => app.set_panel(Panel::Url),
+            Panel::Headers => app.set_panel(Panel::Params),
             Panel::Body => app.set_panel(Panel::Headers),
+            Panel::Variables => app.set_panel(Panel::Body),
+            Panel::Response if graphql => app.set_panel(Panel::Variables),
             Panel::Response => app.set_panel(Panel::Body),
         },
         KeyCode::Char('1') => app.set_panel(Panel::Sidebar),
@@ -33,12 +49,11 @@ pub fn handle_key(app: &mut App, key: KeyEvent) {
         KeyCode::Char('3') => app.set_panel(Panel::Headers),
         KeyCode::Char('4') => app.set_panel(Panel::Body),
         KeyCode::Char('5') => app.set_panel(Panel::Response),
+        KeyCode::Char('6') => app.set_panel(Panel::Params),
+        KeyCode::Char('7') if graphql => app.set_panel(Panel::Variables),
 
         KeyCode::Char('h') => {
-            let cursor = app.cursor();
-            if cursor > 0 {
-                app.set_cursor(cursor - 1);
-            }
+            app.move_cursor_left();
         }
         KeyCode::Char('j') => {
             if app.active_panel == Panel::Sidebar {
@@ -55,11 +70,7 @@ pub fn handle_key(app: &mut App, key: KeyEvent) {
             }
         }
         KeyCode::Char('l') => {
-            let cursor = app.cursor();
-            let buffer_len = app.current_buffer().len();
-            if cursor < buffer_len {
-                app.set_cursor(cursor + 1);
-            }
+            app.move_cursor_right();
         }
         KeyCode::Char('w') => {
             app.move_word_forward();
@@ -110,7 +121,7 @@ pub fn handle_key(app: &mut App, key: KeyEvent) {
                     }
                     app.clear_pending_command();
                 }
-                PendingCommand::Goto => {}
+                PendingCommand::Goto | PendingCommand::Register => {}
             },
             _ => {
                 let buffer = app.current_buffer();
@@ -138,7 +149,7 @@ pub fn handle_key(app: &mut App, key: KeyEvent) {
         KeyCode::Char('G') => {
             let buffer = app.current_buffer();
             let lines: Vec<&str> = buffer.lines().collect();
-            let last_line = lines.len() - 1;
+            let last_line = lines.len().saturating_sub(1);
             let new_cursor = app.line_col_to_cursor(buffer, last_line, 0);
             app.set_cursor(new_cursor);
         }
@@ -148,36 +159,23 @@ pub fn handle_key(app: &mut App, key: KeyEvent) {
                 if let Some(collection_name) = app.selected_collection() {
                     let name = collection_name
                         .strip_suffix(".json")
-                        .unwrap_or(collection_name);
-                    if let Ok(collection) = load_collection(name) {
-                        let request = &collection.saved_request.request;
-                        app.url_buffer = request.url.clone();
-                        app.body_buffer = request.body.clone();
-
-                        let headers_str = request
-                            .headers
-                            .iter()
-                            .map(|(k, v)| format!("{}: {}", k, v))
-                            .collect::<Vec<_>>()
-                            .join("\n");
-                        app.headers_buffer = headers_str;
-
-                        app.current_request = request.clone();
-
-                        app.set_panel(Panel::Url);
+                        .unwrap_or(collection_name)
+                        .to_string();
+                    match load_collection(&name) {
+                        Ok(collection) => {
+                            app.apply_request(collection.saved_request.request);
+                            app.request_name = Some(name.clone());
+                            app.set_panel(Panel::Url);
+                            app.info(format!("loaded {}", name));
+                        }
+                        Err(err) => app.error(format!("E: {}", err)),
                     }
+                } else if let Some(method) = app.selected_grpc_method() {
+                    let method = method.to_string();
+                    app.select_grpc_method(&method);
                 }
             } else {
-                let mut req = Request::new(app.current_request.method, app.url_buffer.clone());
-                for (key, value) in app.parsed_headers() {
-                    req.headers.insert(key, value);
-                }
-                req.body = app.body_buffer.clone();
-                app.current_request = req;
-                let response = send_request(&app.current_request).unwrap_or_default();
-                app.last_response = Some(response);
-                app.update_response_buffer();
-                app.set_panel(Panel::Response);
+                app.send_current_request();
             }
         }
         KeyCode::Char('d') => {
@@ -239,8 +237,11 @@ pub fn handle_key(app: &mut App, key: KeyEvent) {
                 app.set_pending_command(PendingCommand::Yank);
             }
         }
+        KeyCode::Char('"') if app.pending_command.is_none() => {
+            app.set_pending_command(PendingCommand::Register);
+        }
         KeyCode::Char('p') => {
-            if let Some(yanked_content) = &app.yank_register.clone() {
+            if let Some(yanked_content) = &app.register_contents() {
                 let cursor = app.cursor();
                 let current_line = {
                     let buffer = app.current_buffer();
@@ -274,6 +275,30 @@ pub fn handle_key(app: &mut App, key: KeyEvent) {
             app.visual_anchor = Some(cursor);
             app.set_mode(Mode::Visual);
         }
+        KeyCode::Char('E') => {
+            app.should_open_editor = true;
+        }
+        KeyCode::Esc if app.stream.is_some() => {
+            app.stop_stream();
+        }
+        KeyCode::Esc if app.run.is_some() => {
+            app.stop_run();
+        }
+        KeyCode::Esc if app.authorization.is_some() => {
+            app.cancel_authorization();
+        }
+        KeyCode::Esc if app.websocket.is_some() => {
+            let _ = app.websocket_send(Outgoing::Close(1000, String::new()));
+        }
+        KeyCode::Char('t') if app.active_panel == Panel::Response => {
+            app.toggle_response_view(ResponseView::Timing);
+        }
+        KeyCode::Char('T') if app.active_panel == Panel::Response => {
+            app.toggle_response_view(ResponseView::Tests);
+        }
+        KeyCode::Char('x') if app.active_panel == Panel::Response => {
+            app.toggle_response_view(ResponseView::Hex);
+        }
         KeyCode::Char('q') => {
             app.should_quit = true;
         }
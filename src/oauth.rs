@@ -0,0 +1,402 @@
+use base64::Engine;
+use rand::Rng;
+use rand::distributions::Alphanumeric;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// Tokens this close to expiring are refreshed before a request is sent.
+const EXPIRY_MARGIN_SECS: u64 = 30;
+const AUTHORIZE_TIMEOUT: Duration = Duration::from_secs(120);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Grant {
+    #[default]
+    ClientCredentials,
+    Password,
+    RefreshToken,
+    AuthorizationCode,
+}
+
+impl Grant {
+    pub const ALL: [&str; 4] = [
+        "client_credentials",
+        "password",
+        "refresh_token",
+        "authorization_code",
+    ];
+}
+
+impl std::str::FromStr for Grant {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "client_credentials" => Ok(Grant::ClientCredentials),
+            "password" => Ok(Grant::Password),
+            "refresh_token" => Ok(Grant::RefreshToken),
+            "authorization_code" | "pkce" => Ok(Grant::AuthorizationCode),
+            _ => Err(format!("unknown grant: {}", value)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct OAuth2Config {
+    pub grant: Grant,
+    pub token_url: String,
+    pub auth_url: String,
+    pub client_id: String,
+    pub client_secret: String,
+    pub scope: String,
+    pub username: String,
+    pub password: String,
+    pub refresh_token: String,
+    pub redirect_port: u16,
+}
+
+impl OAuth2Config {
+    pub const FIELDS: [&str; 9] = [
+        "token_url",
+        "auth_url",
+        "client_id",
+        "client_secret",
+        "scope",
+        "username",
+        "password",
+        "refresh_token",
+        "redirect_port",
+    ];
+
+    pub fn set(&mut self, field: &str, value: &str) -> Result<(), String> {
+        let value = value.to_string();
+        match field {
+            "token_url" => self.token_url = value,
+            "auth_url" => self.auth_url = value,
+            "client_id" => self.client_id = value,
+            "client_secret" => self.client_secret = value,
+            "scope" => self.scope = value,
+            "username" => self.username = value,
+            "password" => self.password = value,
+            "refresh_token" => self.refresh_token = value,
+            "redirect_port" => {
+                self.redirect_port = value
+                    .parse()
+                    .map_err(|_| format!("invalid port: {}", value))?
+            }
+            "grant" => self.grant = value.parse()?,
+            _ => return Err(format!("unknown oauth field: {}", field)),
+        }
+        Ok(())
+    }
+
+    pub fn map_values(&self, f: impl Fn(&str) -> String) -> OAuth2Config {
+        OAuth2Config {
+            grant: self.grant,
+            token_url: f(&self.token_url),
+            auth_url: f(&self.auth_url),
+            client_id: f(&self.client_id),
+            client_secret: f(&self.client_secret),
+            scope: f(&self.scope),
+            username: f(&self.username),
+            password: f(&self.password),
+            refresh_token: f(&self.refresh_token),
+            redirect_port: self.redirect_port,
+        }
+    }
+
+    // Identifies the cached token for this client within an environment.
+    pub fn cache_key(&self, environment: &str) -> String {
+        format!("{}|{}|{}", environment, self.token_url, self.client_id)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Token {
+    pub access_token: String,
+    #[serde(default)]
+    pub refresh_token: Option<String>,
+    #[serde(default)]
+    pub expires_at: Option<u64>,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+impl Token {
+    pub fn seconds_left(&self) -> Option<i64> {
+        self.expires_at.map(|at| at as i64 - now_secs() as i64)
+    }
+
+    pub fn needs_refresh(&self) -> bool {
+        self.seconds_left()
+            .is_some_and(|left| left <= EXPIRY_MARGIN_SECS as i64)
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    refresh_token: Option<String>,
+    #[serde(default)]
+    expires_in: Option<u64>,
+}
+
+fn request_token(
+    config: &OAuth2Config,
+    mut params: Vec<(&str, String)>,
+) -> Result<Token, Box<dyn std::error::Error>> {
+    if config.token_url.is_empty() {
+        return Err("oauth token_url is not set".into());
+    }
+    params.push(("client_id", config.client_id.clone()));
+    if !config.client_secret.is_empty() {
+        params.push(("client_secret", config.client_secret.clone()));
+    }
+    if !config.scope.is_empty() {
+        params.push(("scope", config.scope.clone()));
+    }
+
+    let resp = reqwest::blocking::Client::new()
+        .post(&config.token_url)
+        .header("Accept", "application/json")
+        .form(&params)
+        .send()?;
+    let status = resp.status();
+    let body = resp.text()?;
+    if !status.is_success() {
+        return Err(format!("token request failed ({}): {}", status, body).into());
+    }
+
+    let token: TokenResponse = serde_json::from_str(&body)?;
+    Ok(Token {
+        access_token: token.access_token,
+        refresh_token: token.refresh_token,
+        expires_at: token.expires_in.map(|secs| now_secs() + secs),
+    })
+}
+
+pub fn refresh(
+    config: &OAuth2Config,
+    refresh_token: &str,
+) -> Result<Token, Box<dyn std::error::Error>> {
+    let mut token = request_token(
+        config,
+        vec![
+            ("grant_type", "refresh_token".to_string()),
+            ("refresh_token", refresh_token.to_string()),
+        ],
+    )?;
+    // Servers that don't rotate refresh tokens leave it out of the response.
+    if token.refresh_token.is_none() {
+        token.refresh_token = Some(refresh_token.to_string());
+    }
+    Ok(token)
+}
+
+// `open_url` is called with the authorization URL for the user to visit.
+pub fn acquire(
+    config: &OAuth2Config,
+    open_url: impl Fn(&str),
+) -> Result<Token, Box<dyn std::error::Error>> {
+    match config.grant {
+        Grant::ClientCredentials => request_token(
+            config,
+            vec![("grant_type", "client_credentials".to_string())],
+        ),
+        Grant::Password => request_token(
+            config,
+            vec![
+                ("grant_type", "password".to_string()),
+                ("username", config.username.clone()),
+                ("password", config.password.clone()),
+            ],
+        ),
+        Grant::RefreshToken => {
+            if config.refresh_token.is_empty() {
+                return Err("oauth refresh_token is not set".into());
+            }
+            refresh(config, &config.refresh_token)
+        }
+        Grant::AuthorizationCode => Authorization::start(config, open_url)?.wait(),
+    }
+}
+
+pub fn open_in_browser(url: &str) {
+    let program = if cfg!(target_os = "macos") { "open" } else { "xdg-open" };
+    let _ = std::process::Command::new(program)
+        .arg(url)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn();
+}
+
+fn random_string(len: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(len)
+        .map(char::from)
+        .collect()
+}
+
+// The browser half of the authorization code flow, running on a worker thread that waits for
+// the redirect and exchanges the code, so the TUI can keep drawing and cancel it.
+pub struct Authorization {
+    receiver: Receiver<Result<Token, String>>,
+    cancel: Arc<AtomicBool>,
+}
+
+impl Authorization {
+    pub fn start(
+        config: &OAuth2Config,
+        open_url: impl Fn(&str),
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let (sender, receiver) = mpsc::channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let wait = authorize_with_pkce(config, open_url)?;
+        let cancelled = cancel.clone();
+        std::thread::spawn(move || {
+            let _ = sender.send(wait(&cancelled).map_err(|e| e.to_string()));
+        });
+        Ok(Self { receiver, cancel })
+    }
+
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+
+    // None while the user hasn't finished in the browser.
+    pub fn poll(&self) -> Option<Result<Token, String>> {
+        match self.receiver.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err("authorization stopped".to_string())),
+        }
+    }
+
+    pub fn wait(self) -> Result<Token, Box<dyn std::error::Error>> {
+        let result = self.receiver.recv().map_err(|_| "authorization stopped")?;
+        Ok(result?)
+    }
+}
+
+type Wait = Box<dyn FnOnce(&AtomicBool) -> Result<Token, Box<dyn std::error::Error>> + Send>;
+
+// Opens the authorization URL and returns what is left: waiting for the code and exchanging it.
+fn authorize_with_pkce(
+    config: &OAuth2Config,
+    open_url: impl Fn(&str),
+) -> Result<Wait, Box<dyn std::error::Error>> {
+    if config.auth_url.is_empty() {
+        return Err("oauth auth_url is not set".into());
+    }
+
+    let listener = TcpListener::bind(("127.0.0.1", config.redirect_port))?;
+    let redirect_uri = format!("http://127.0.0.1:{}/callback", listener.local_addr()?.port());
+    let verifier = random_string(64);
+    let challenge = base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .encode(Sha256::digest(verifier.as_bytes()));
+    let state = random_string(24);
+
+    let mut auth_url = reqwest::Url::parse(&config.auth_url)?;
+    auth_url
+        .query_pairs_mut()
+        .append_pair("response_type", "code")
+        .append_pair("client_id", &config.client_id)
+        .append_pair("redirect_uri", &redirect_uri)
+        .append_pair("code_challenge", &challenge)
+        .append_pair("code_challenge_method", "S256")
+        .append_pair("state", &state);
+    if !config.scope.is_empty() {
+        auth_url.query_pairs_mut().append_pair("scope", &config.scope);
+    }
+    open_url(auth_url.as_str());
+
+    let config = config.clone();
+    Ok(Box::new(move |cancel: &AtomicBool| {
+        let code = wait_for_code(&listener, &state, cancel)?;
+        request_token(
+            &config,
+            vec![
+                ("grant_type", "authorization_code".to_string()),
+                ("code", code),
+                ("redirect_uri", redirect_uri),
+                ("code_verifier", verifier),
+            ],
+        )
+    }))
+}
+
+fn wait_for_code(
+    listener: &TcpListener,
+    state: &str,
+    cancel: &AtomicBool,
+) -> Result<String, Box<dyn std::error::Error>> {
+    listener.set_nonblocking(true)?;
+    let deadline = Instant::now() + AUTHORIZE_TIMEOUT;
+
+    loop {
+        let mut stream = match listener.accept() {
+            Ok((stream, _)) => stream,
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                if cancel.load(Ordering::Relaxed) {
+                    return Err("authorization cancelled".into());
+                }
+                if Instant::now() > deadline {
+                    return Err("timed out waiting for the authorization redirect".into());
+                }
+                std::thread::sleep(Duration::from_millis(100));
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+        stream.set_nonblocking(false)?;
+
+        let mut request_line = String::new();
+        BufReader::new(&stream).read_line(&mut request_line)?;
+        let target = request_line.split_whitespace().nth(1).unwrap_or("/");
+        let url = reqwest::Url::parse(&format!("http://127.0.0.1{}", target))?;
+        let param = |name: &str| {
+            url.query_pairs()
+                .find(|(k, _)| k == name)
+                .map(|(_, v)| v.to_string())
+        };
+
+        // Browsers also ask for /favicon.ico; only the callback carries a code or error.
+        let result = match (param("code"), param("error")) {
+            (_, Some(error)) => Err(format!("authorization failed: {}", error)),
+            (Some(_), _) if param("state").as_deref() != Some(state) => {
+                Err("authorization state mismatch".to_string())
+            }
+            (Some(code), _) => Ok(code),
+            (None, None) => {
+                let _ = stream.write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n");
+                continue;
+            }
+        };
+
+        let page = match &result {
+            Ok(_) => "Authorization complete. You can return to vreq.",
+            Err(e) => e.as_str(),
+        };
+        let _ = write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            page.len(),
+            page
+        );
+        return result.map_err(|e| e.into());
+    }
+}
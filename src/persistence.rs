@@ -1,7 +1,12 @@
-use crate::http::Request;
+use crate::graphql::Schema;
+use crate::http::{self, Header, Request};
+use crate::oauth::Token;
+use crate::script::{Hook, Script};
+use crate::timing::Timing;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::path::PathBuf;
-use std::fs::{create_dir_all, read, write, read_dir};
+use std::fs::{create_dir_all, read, write, read_dir, read_to_string, OpenOptions};
 use chrono::offset::Utc;
 use std::time::SystemTime;
 
@@ -31,6 +36,16 @@ impl SavedRequest {
     }
 }
 
+// A named set of variables in `environments/<name>.json`, activated with `:env <name>`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Environment {
+    pub name: String,
+    pub variables: BTreeMap<String, String>,
+    // Variables last set by a request's captures, and where each value came from.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub captured: BTreeMap<String, String>,
+}
+
 impl Collection {
     pub fn new(name: String, request: Request) -> Self {
         Collection {
@@ -40,9 +55,24 @@ impl Collection {
     }
 }
 
-pub fn get_data_dir() -> Result<PathBuf, Box<dyn std::error::Error>> {
+fn not_found(err: std::io::Error, kind: &str, name: &str) -> Box<dyn std::error::Error> {
+    if err.kind() == std::io::ErrorKind::NotFound {
+        format!("{} not found: {}", kind, name).into()
+    } else {
+        err.into()
+    }
+}
+
+pub fn get_base_dir() -> Result<PathBuf, Box<dyn std::error::Error>> {
     let home_dir = std::env::var("HOME").unwrap_or("".to_string());
-    let data_dir = PathBuf::from(format!("{}/.local/vreq/collections", home_dir));
+    let base_dir = PathBuf::from(format!("{}/.local/vreq", home_dir));
+    create_dir_all(base_dir.clone())?;
+    Ok(base_dir)
+}
+
+pub fn get_data_dir() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let mut data_dir = get_base_dir()?;
+    data_dir.push("collections");
     create_dir_all(data_dir.clone())?;
     Ok(data_dir)
 }
@@ -51,7 +81,7 @@ pub fn load_collection(name: &str) -> Result<Collection, Box<dyn std::error::Err
     let file_name = format!("{}.json", name);
     let mut data_dir = get_data_dir()?;
     data_dir.push(file_name);
-    let contents = read(data_dir)?;
+    let contents = read(data_dir).map_err(|e| not_found(e, "collection", name))?;
     let collection: Collection = serde_json::from_slice(&contents)?;
     Ok(collection)
 }
@@ -78,3 +108,288 @@ pub fn list_collections() -> Result<Vec<String>, Box<dyn std::error::Error>> {
 
     Ok(file_names)
 }
+
+// The requests a name covers, in run order: a single saved request, or every
+// request under a folder such as `users`, sorted by path.
+pub fn list_requests(name: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let name = name.trim_matches('/');
+    let mut path = get_data_dir()?;
+    path.push(format!("{}.json", name));
+    if path.is_file() {
+        return Ok(vec![name.to_string()]);
+    }
+    path.set_extension("");
+    if !path.is_dir() {
+        return Err(format!("collection not found: {}", name).into());
+    }
+
+    let mut names = Vec::new();
+    let mut dirs = vec![(path, name.to_string())];
+    while let Some((dir, prefix)) = dirs.pop() {
+        for entry in read_dir(&dir)? {
+            let entry = entry?;
+            let Some(file_name) = entry.file_name().to_str().map(str::to_owned) else {
+                continue;
+            };
+            if entry.file_type()?.is_dir() {
+                dirs.push((entry.path(), format!("{}/{}", prefix, file_name)));
+            } else if let Some(stem) = file_name.strip_suffix(".json") {
+                names.push(format!("{}/{}", prefix, stem));
+            }
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+// The `pre.rhai` or `post.rhai` scripts of the folders a saved request is in, outermost first.
+pub fn collection_scripts(name: &str, hook: Hook) -> Result<Vec<Script>, Box<dyn std::error::Error>> {
+    let data_dir = get_data_dir()?;
+    let mut scripts = Vec::new();
+    let parts: Vec<&str> = name.split('/').collect();
+    let mut folder = String::new();
+    for part in &parts[..parts.len() - 1] {
+        if !folder.is_empty() {
+            folder.push('/');
+        }
+        folder.push_str(part);
+        let path = data_dir.join(&folder).join(format!("{}.rhai", hook.name()));
+        if path.is_file() {
+            scripts.push((format!("{}/{}.rhai", folder, hook.name()), read_to_string(path)?));
+        }
+    }
+    Ok(scripts)
+}
+
+pub fn get_environments_dir() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let mut env_dir = get_base_dir()?;
+    env_dir.push("environments");
+    create_dir_all(env_dir.clone())?;
+    Ok(env_dir)
+}
+
+pub fn load_environment(name: &str) -> Result<Environment, Box<dyn std::error::Error>> {
+    let mut path = get_environments_dir()?;
+    path.push(format!("{}.json", name));
+    let contents = read(path).map_err(|e| not_found(e, "environment", name))?;
+    let environment: Environment = serde_json::from_slice(&contents)?;
+    Ok(environment)
+}
+
+pub fn save_environment(environment: &Environment) -> Result<(), Box<dyn std::error::Error>> {
+    let mut path = get_environments_dir()?;
+    path.push(format!("{}.json", environment.name));
+    write(path, serde_json::to_string_pretty(environment)?)?;
+    Ok(())
+}
+
+pub fn list_environments() -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let mut names: Vec<String> = Vec::new();
+    for entry in read_dir(get_environments_dir()?)? {
+        let file_name = entry?.file_name();
+        if let Some(name) = file_name.to_str().and_then(|n| n.strip_suffix(".json")) {
+            names.push(name.to_owned());
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+const COMMAND_HISTORY_LIMIT: usize = 200;
+
+// Auth commands carry credentials, so they are never remembered.
+pub fn is_secret_command(command: &str) -> bool {
+    matches!(command.split_whitespace().next(), Some("auth" | "oauth"))
+}
+
+pub fn load_command_history() -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let mut path = get_base_dir()?;
+    path.push("command_history");
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let contents = read_to_string(path)?;
+    let history: Vec<String> = contents
+        .lines()
+        .filter(|l| !is_secret_command(l))
+        .map(|l| l.to_string())
+        .collect();
+    let skip = history.len().saturating_sub(COMMAND_HISTORY_LIMIT);
+    Ok(history.into_iter().skip(skip).collect())
+}
+
+// Keeps the file to the most recent COMMAND_HISTORY_LIMIT commands.
+pub fn append_command_history(command: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut path = get_base_dir()?;
+    path.push("command_history");
+    let contents = if path.exists() { read_to_string(&path)? } else { String::new() };
+    let mut history: Vec<&str> = contents.lines().filter(|l| !is_secret_command(l)).collect();
+    history.push(command);
+    let skip = history.len().saturating_sub(COMMAND_HISTORY_LIMIT);
+    write(path, format!("{}\n", history[skip..].join("\n")))?;
+    Ok(())
+}
+
+const HEADER_HISTORY_LIMIT: usize = 200;
+
+// Headers whose values are credentials; only their names are remembered.
+fn is_secret(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    matches!(name.as_str(), "authorization" | "proxy-authorization" | "cookie")
+        || ["token", "key", "secret", "password", "session"].iter().any(|word| name.contains(word))
+}
+
+// The header as it is kept in the history: enabled, and without the value of a secret.
+pub fn remembered_header(header: &Header) -> Header {
+    Header {
+        name: header.name.clone(),
+        value: if is_secret(&header.name) { String::new() } else { header.value.clone() },
+        enabled: true,
+    }
+}
+
+pub fn load_header_history() -> Result<Vec<Header>, Box<dyn std::error::Error>> {
+    let mut path = get_base_dir()?;
+    path.push("header_history");
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let contents = read_to_string(path)?;
+    let mut headers: Vec<Header> = Vec::new();
+    for header in http::parse_headers(&contents).iter().map(remembered_header) {
+        if !headers.contains(&header) {
+            headers.push(header);
+        }
+    }
+    let skip = headers.len().saturating_sub(HEADER_HISTORY_LIMIT);
+    Ok(headers.split_off(skip))
+}
+
+// Rewrites the whole file, which keeps it to the most recent HEADER_HISTORY_LIMIT headers and
+// drops secrets saved by older versions.
+pub fn save_header_history(headers: &[Header]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut path = get_base_dir()?;
+    path.push("header_history");
+    let skip = headers.len().saturating_sub(HEADER_HISTORY_LIMIT);
+    write(path, format!("{}\n", http::format_headers(&headers[skip..])))?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub sent_at: String,
+    pub method: String,
+    pub url: String,
+    pub status: u16,
+    #[serde(default)]
+    pub timing: Timing,
+}
+
+impl HistoryEntry {
+    pub fn new(method: String, url: String, status: u16, timing: Timing) -> Self {
+        let datetime: chrono::DateTime<Utc> = SystemTime::now().into();
+        Self {
+            sent_at: datetime.format("%Y-%m-%d %H:%M:%S").to_string(),
+            method,
+            url,
+            status,
+            timing,
+        }
+    }
+}
+
+pub const HISTORY_LIMIT: usize = 500;
+
+// The most recent HISTORY_LIMIT entries, oldest first.
+pub fn load_history() -> Result<Vec<HistoryEntry>, Box<dyn std::error::Error>> {
+    let mut path = get_base_dir()?;
+    path.push("history");
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let contents = read_to_string(path)?;
+    let lines: Vec<&str> = contents.lines().collect();
+    let skip = lines.len().saturating_sub(HISTORY_LIMIT);
+    Ok(lines[skip..]
+        .iter()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+pub fn append_history(entry: &HistoryEntry) -> Result<(), Box<dyn std::error::Error>> {
+    use std::io::Write;
+    let mut path = get_base_dir()?;
+    path.push("history");
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", serde_json::to_string(entry)?)?;
+    Ok(())
+}
+
+// Large response bodies are streamed here instead of being kept in memory.
+pub fn response_file_path() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let mut path = get_base_dir()?;
+    path.push("responses");
+    create_dir_all(&path)?;
+    let datetime: chrono::DateTime<Utc> = SystemTime::now().into();
+    path.push(format!("{}.body", datetime.format("%Y%m%d-%H%M%S%.3f")));
+    Ok(path)
+}
+
+pub fn load_tokens() -> Result<HashMap<String, Token>, Box<dyn std::error::Error>> {
+    let mut path = get_base_dir()?;
+    path.push("tokens.json");
+    if !path.exists() {
+        return Ok(HashMap::new());
+    }
+    let contents = read(path)?;
+    Ok(serde_json::from_slice(&contents)?)
+}
+
+pub fn save_tokens(tokens: &HashMap<String, Token>) -> Result<(), Box<dyn std::error::Error>> {
+    use std::io::Write;
+    let mut path = get_base_dir()?;
+    path.push("tokens.json");
+    let contents = serde_json::to_string(tokens)?;
+    // Tokens are credentials, so only the user may read the file.
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        if path.exists() {
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+        }
+    }
+    let mut file = options.open(&path)?;
+    file.write_all(contents.as_bytes())?;
+    Ok(())
+}
+
+// Introspected GraphQL schemas, one file per endpoint URL.
+fn schema_path(url: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let mut path = get_base_dir()?;
+    path.push("graphql");
+    create_dir_all(&path)?;
+    let name: String = url
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' { c } else { '_' })
+        .collect();
+    path.push(format!("{}.json", name));
+    Ok(path)
+}
+
+pub fn load_schema(url: &str) -> Result<Option<Schema>, Box<dyn std::error::Error>> {
+    let path = schema_path(url)?;
+    if !path.exists() {
+        return Ok(None);
+    }
+    let contents = read(path)?;
+    Ok(Some(serde_json::from_slice(&contents)?))
+}
+
+pub fn save_schema(url: &str, schema: &Schema) -> Result<(), Box<dyn std::error::Error>> {
+    let contents = serde_json::to_string(schema)?;
+    write(schema_path(url)?, contents)?;
+    Ok(())
+}
@@ -0,0 +1,190 @@
+use percent_encoding::{AsciiSet, CONTROLS, percent_decode_str, utf8_percent_encode};
+use serde::{Deserialize, Serialize};
+
+// Braces stay readable so `{{var}}` placeholders survive until they are substituted.
+const QUERY_COMPONENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'&')
+    .add(b'+')
+    .add(b'<')
+    .add(b'=')
+    .add(b'>')
+    .add(b'`');
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueryParam {
+    pub key: String,
+    pub value: Option<String>,
+    #[serde(default = "enabled_default")]
+    pub enabled: bool,
+}
+
+fn enabled_default() -> bool {
+    true
+}
+
+impl QueryParam {
+    fn to_line(&self) -> String {
+        let line = match &self.value {
+            Some(value) => format!("{} = {}", self.key, value),
+            None => self.key.clone(),
+        };
+        if self.enabled { line } else { format!("# {}", line) }
+    }
+
+    fn encode(&self) -> String {
+        let key = utf8_percent_encode(&self.key, QUERY_COMPONENT);
+        match &self.value {
+            Some(value) => format!("{}={}", key, utf8_percent_encode(value, QUERY_COMPONENT)),
+            None => key.to_string(),
+        }
+    }
+}
+
+fn decode(component: &str) -> String {
+    let component = component.replace('+', " ");
+    percent_decode_str(&component).decode_utf8_lossy().to_string()
+}
+
+fn split_url(url: &str) -> (&str, Option<&str>, Option<&str>) {
+    let (rest, fragment) = match url.split_once('#') {
+        Some((rest, fragment)) => (rest, Some(fragment)),
+        None => (url, None),
+    };
+    match rest.split_once('?') {
+        Some((base, query)) => (base, Some(query), fragment),
+        None => (rest, None, fragment),
+    }
+}
+
+pub fn params_from_url(url: &str) -> Vec<QueryParam> {
+    let (_, query, _) = split_url(url);
+    query
+        .unwrap_or("")
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = match pair.split_once('=') {
+                Some((key, value)) => (key, Some(decode(value))),
+                None => (pair, None),
+            };
+            QueryParam { key: decode(key), value, enabled: true }
+        })
+        .collect()
+}
+
+pub fn url_with_params(url: &str, params: &[QueryParam]) -> String {
+    let (base, _, fragment) = split_url(url);
+    let query = params
+        .iter()
+        .filter(|p| p.enabled)
+        .map(QueryParam::encode)
+        .collect::<Vec<_>>()
+        .join("&");
+
+    let mut result = base.to_string();
+    if !query.is_empty() {
+        result.push('?');
+        result.push_str(&query);
+    }
+    if let Some(fragment) = fragment {
+        result.push('#');
+        result.push_str(fragment);
+    }
+    result
+}
+
+pub fn parse_params(text: &str) -> Vec<QueryParam> {
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let line = line.trim();
+            let (enabled, line) = match line.strip_prefix('#') {
+                Some(rest) => (false, rest.trim_start()),
+                None => (true, line),
+            };
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), Some(value.trim().to_string())),
+                None => (line, None),
+            };
+            QueryParam { key: key.to_string(), value, enabled }
+        })
+        .collect()
+}
+
+pub fn format_params(params: &[QueryParam]) -> String {
+    params.iter().map(QueryParam::to_line).collect::<Vec<_>>().join("\n")
+}
+
+// Keeps disabled rows where they were while taking the enabled rows from the URL.
+pub fn merge_disabled(url_params: Vec<QueryParam>, previous: &[QueryParam]) -> Vec<QueryParam> {
+    let mut from_url = url_params.into_iter();
+    let mut merged = Vec::new();
+    for param in previous {
+        if !param.enabled {
+            merged.push(param.clone());
+        } else if let Some(next) = from_url.next() {
+            merged.push(next);
+        }
+    }
+    merged.extend(from_url);
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn param(key: &str, value: Option<&str>, enabled: bool) -> QueryParam {
+        QueryParam { key: key.to_string(), value: value.map(str::to_string), enabled }
+    }
+
+    #[test]
+    fn params_round_trip_through_the_url() {
+        let url = "https://x.test/a?q=a+b&flag&name=%7B%7Bv%7D%7D#top";
+        let params = params_from_url(url);
+        assert_eq!(
+            params,
+            vec![
+                param("q", Some("a b"), true),
+                param("flag", None, true),
+                param("name", Some("{{v}}"), true),
+            ]
+        );
+        assert_eq!(url_with_params(url, &params), "https://x.test/a?q=a%20b&flag&name={{v}}#top");
+    }
+
+    #[test]
+    fn disabled_params_are_left_out_of_the_url() {
+        let params = parse_params("a = 1\n# b = 2\n\nc");
+        assert_eq!(
+            params,
+            vec![param("a", Some("1"), true), param("b", Some("2"), false), param("c", None, true)]
+        );
+        assert_eq!(format_params(&params), "a = 1\n# b = 2\nc");
+        assert_eq!(url_with_params("http://x/?old=1", &params), "http://x/?a=1&c");
+        assert_eq!(url_with_params("http://x/?old=1", &[]), "http://x/");
+    }
+
+    #[test]
+    fn merge_keeps_disabled_rows_in_place() {
+        let previous = parse_params("a = 1\n# b = 2\nc = 3");
+        let from_url = params_from_url("http://x/?a=9&c=8&d=7");
+        assert_eq!(
+            merge_disabled(from_url, &previous),
+            vec![
+                param("a", Some("9"), true),
+                param("b", Some("2"), false),
+                param("c", Some("8"), true),
+                param("d", Some("7"), true),
+            ]
+        );
+
+        // Rows removed from the URL disappear, disabled ones stay.
+        let merged = merge_disabled(params_from_url("http://x/?a=1"), &previous);
+        assert_eq!(merged, vec![param("a", Some("1"), true), param("b", Some("2"), false)]);
+    }
+}
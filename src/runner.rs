@@ -0,0 +1,617 @@
+use crate::app::App;
+use crate::capture::Capture;
+use crate::grpc;
+use crate::http::{DEFAULT_RESPONSE_LIMIT, Request, Response, send_request};
+use crate::script;
+use crate::oauth::Token;
+use crate::persistence::{
+    Environment, HistoryEntry, list_requests, load_collection,
+};
+use crate::websocket;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// The outcome of sending one request and checking its assertions.
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub name: String,
+    pub method: String,
+    pub url: String,
+    // The URL as saved, placeholders and all; JUnit reports use it so resolved secrets such as
+    // query-string keys stay out of CI artifacts.
+    pub template: String,
+    pub status: Option<u16>,
+    pub duration_ms: u128,
+    // Set when the request could not be resolved or sent.
+    pub error: Option<String>,
+    // Each assertion with the reason it failed, if it did.
+    pub results: Vec<(String, Option<String>)>,
+    pub logs: Vec<String>,
+}
+
+impl Report {
+    pub fn new(name: &str, request: &Request) -> Self {
+        Report {
+            name: name.to_string(),
+            method: request.method.to_string(),
+            url: request.url.clone(),
+            template: request.url.clone(),
+            status: None,
+            duration_ms: 0,
+            error: None,
+            results: Vec::new(),
+            logs: Vec::new(),
+        }
+    }
+
+    pub fn failed(&self) -> bool {
+        self.error.is_some() || self.results.iter().any(|(_, failure)| failure.is_some())
+    }
+
+    pub fn check(&mut self, request: &Request, response: &Response) {
+        self.status = Some(response.status);
+        self.duration_ms = response.duration_ms;
+        self.results = request
+            .assertions
+            .iter()
+            .map(|assertion| (assertion.to_string(), assertion.check(response).err()))
+            .collect();
+    }
+
+    pub fn add_script(&mut self, output: &script::Output) {
+        self.logs.extend(output.logs.iter().cloned());
+        self.results.extend(output.assertions.iter().cloned());
+    }
+
+    // Captures that found nothing fail the request, since later requests depend on them.
+    pub fn add_captures(&mut self, captured: &[(Capture, Option<String>)]) {
+        for (capture, failure) in captured {
+            if failure.is_some() {
+                self.results.push((format!("capture {}", capture), failure.clone()));
+            }
+        }
+    }
+}
+
+// Streaming protocols have no single response to check.
+pub fn testable(request: &Request) -> Result<(), String> {
+    let accepts_events = request.headers.iter().any(|h| {
+        h.enabled && h.name.eq_ignore_ascii_case("accept") && h.value.contains("text/event-stream")
+    });
+    if websocket::is_websocket_url(&request.url) || grpc::is_grpc_url(&request.url) || accepts_events {
+        return Err("only plain HTTP requests can be tested".to_string());
+    }
+    Ok(())
+}
+
+pub fn send(app: &mut App, request: &Request) -> Result<Response, Box<dyn std::error::Error>> {
+    testable(request)?;
+    let limit = app.config.response_limit.unwrap_or(DEFAULT_RESPONSE_LIMIT);
+    let response = send_request(request, limit)?;
+    app.record_history(HistoryEntry::new(
+        request.method.to_string(),
+        request.url.clone(),
+        response.status,
+        response.timing.clone(),
+    ));
+    Ok(response)
+}
+
+// reqwest appends the resolved URL to its errors, which may carry secrets from variables.
+fn failure(err: Box<dyn std::error::Error>) -> String {
+    let err = match err.downcast::<std::io::Error>() {
+        Ok(err) if err.get_ref().is_some_and(|inner| inner.is::<reqwest::Error>()) => {
+            err.into_inner().expect("checked above")
+        }
+        Ok(err) => return err.to_string(),
+        Err(err) => err,
+    };
+    match err.downcast::<reqwest::Error>() {
+        Ok(err) => err.without_url().to_string(),
+        Err(err) => err.to_string(),
+    }
+}
+
+// Loads the saved request into `app` so it resolves exactly as the TUI would send it.
+pub fn run_request(app: &mut App, name: &str) -> Report {
+    let saved = match load_collection(name) {
+        Ok(collection) => collection.saved_request.request,
+        Err(err) => {
+            let mut report = Report::new(name, &Request::default());
+            report.error = Some(err.to_string());
+            return report;
+        }
+    };
+    let mut report = Report::new(name, &saved);
+    app.apply_request(saved);
+    app.request_name = Some(name.to_string());
+    let request = match app.resolve_request() {
+        Ok(request) => request,
+        Err(err) => {
+            report.error = Some(err.to_string());
+            return report;
+        }
+    };
+    report.url = request.url.clone();
+    match send(app, &request) {
+        Ok(response) => {
+            report.check(&request, &response);
+            let captured = app.capture(&request, &response);
+            report.add_captures(&captured);
+            app.run_post_scripts(&request, &response);
+            if let Some(path) = &response.body_file {
+                let _ = std::fs::remove_file(path);
+            }
+        }
+        Err(err) => report.error = Some(failure(err)),
+    }
+    report.add_script(&app.script_output);
+    report
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    // A CSV file with a header row, or a JSON array of objects; each row is one iteration.
+    pub data: Option<String>,
+    pub iterations: Option<usize>,
+    pub delay_ms: u64,
+    pub stop_on_failure: bool,
+}
+
+impl Options {
+    // Takes `arg` (and its value from `args`) if it is a runner option.
+    pub fn take<'a>(
+        &mut self,
+        arg: &str,
+        args: &mut impl Iterator<Item = &'a str>,
+    ) -> Result<bool, String> {
+        match arg {
+            "--data" | "-d" => {
+                self.data = Some(args.next().ok_or("--data needs a file path")?.to_string());
+            }
+            "--iterations" | "-n" => {
+                let value = args.next().ok_or("--iterations needs a number")?;
+                let count = value
+                    .parse()
+                    .map_err(|_| format!("invalid iteration count: {}", value))?;
+                self.iterations = Some(count);
+            }
+            "--delay" => {
+                let value = args.next().ok_or("--delay needs milliseconds")?;
+                self.delay_ms = value.parse().map_err(|_| format!("invalid delay: {}", value))?;
+            }
+            "--stop-on-failure" | "--stop" => self.stop_on_failure = true,
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+}
+
+// One pass over the collection, with the data row its variables came from.
+#[derive(Debug, Clone, Default)]
+pub struct Iteration {
+    pub number: usize,
+    pub row: BTreeMap<String, String>,
+    pub reports: Vec<Report>,
+}
+
+impl Iteration {
+    pub fn failed(&self) -> usize {
+        self.reports.iter().filter(|r| r.failed()).count()
+    }
+}
+
+fn cell(value: Value) -> String {
+    match value {
+        Value::String(text) => text,
+        Value::Null => String::new(),
+        value => value.to_string(),
+    }
+}
+
+pub fn load_data(path: &str) -> Result<Vec<BTreeMap<String, String>>, Box<dyn std::error::Error>> {
+    let path = match path.strip_prefix("~/") {
+        Some(rest) => format!("{}/{}", std::env::var("HOME").unwrap_or_default(), rest),
+        None => path.to_string(),
+    };
+    let contents =
+        std::fs::read_to_string(&path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+    if path.ends_with(".json") {
+        let rows: Vec<serde_json::Map<String, Value>> = serde_json::from_str(&contents)
+            .map_err(|e| format!("{}: expected an array of objects: {}", path, e))?;
+        return Ok(rows
+            .into_iter()
+            .map(|row| row.into_iter().map(|(k, v)| (k, cell(v))).collect())
+            .collect());
+    }
+    let mut reader = csv::Reader::from_reader(contents.as_bytes());
+    let headers = reader.headers()?.clone();
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| format!("{}: {}", path, e))?;
+        let cells = headers.iter().zip(record.iter());
+        rows.push(cells.map(|(k, v)| (k.to_string(), v.to_string())).collect());
+    }
+    Ok(rows)
+}
+
+// Sleeps in short steps so a stopped run doesn't wait out the whole delay.
+fn pause(ms: u64, stop: &AtomicBool) {
+    let until = Instant::now() + Duration::from_millis(ms);
+    while !stop.load(Ordering::Relaxed) && Instant::now() < until {
+        std::thread::sleep(Duration::from_millis(20).min(until - Instant::now()));
+    }
+}
+
+// Runs the requests under `name` in order, once per data row (or `iterations` times), binding
+// each row's columns as variables. `progress` sees the current iteration after every request.
+pub fn run(
+    app: &mut App,
+    name: &str,
+    options: &Options,
+    stop: &AtomicBool,
+    progress: &mut dyn FnMut(&Iteration),
+) -> Result<Vec<Iteration>, Box<dyn std::error::Error>> {
+    let names = list_requests(name.strip_suffix(".json").unwrap_or(name))?;
+    let rows = match &options.data {
+        Some(path) => load_data(path)?,
+        None => Vec::new(),
+    };
+    if options.data.is_some() && rows.is_empty() {
+        return Err("the data file has no rows".into());
+    }
+    let count = options.iterations.unwrap_or(rows.len().max(1));
+
+    let mut iterations: Vec<Iteration> = Vec::new();
+    'run: for number in 1..=count {
+        let row = match rows.len() {
+            0 => BTreeMap::new(),
+            len => rows[(number - 1) % len].clone(),
+        };
+        app.data_variables = row.clone();
+        iterations.push(Iteration { number, row, reports: Vec::new() });
+        for (i, name) in names.iter().enumerate() {
+            if stop.load(Ordering::Relaxed) {
+                break 'run;
+            }
+            if options.delay_ms > 0 && (number > 1 || i > 0) {
+                pause(options.delay_ms, stop);
+            }
+            let report = run_request(app, name);
+            let failed = report.failed();
+            let iteration = iterations.last_mut().expect("pushed above");
+            iteration.reports.push(report);
+            progress(iteration);
+            if failed && options.stop_on_failure {
+                break 'run;
+            }
+        }
+    }
+    app.data_variables.clear();
+    Ok(iterations)
+}
+
+fn push_report(text: &mut String, report: &Report) {
+    let outcome = if report.failed() { "FAIL" } else { "PASS" };
+    let status = report.status.map_or("-".to_string(), |s| s.to_string());
+    text.push_str(&format!(
+        "{} {} {} {} ({}, {} ms)\n",
+        outcome, report.name, report.method, report.url, status, report.duration_ms
+    ));
+    if let Some(err) = &report.error {
+        text.push_str(&format!("  error: {}\n", err));
+    }
+    for line in &report.logs {
+        text.push_str(&format!("  log: {}\n", line));
+    }
+    for (assertion, failure) in &report.results {
+        match failure {
+            Some(reason) => text.push_str(&format!("  ✗ {}: {}\n", assertion, reason)),
+            None => text.push_str(&format!("  ✓ {}\n", assertion)),
+        }
+    }
+}
+
+pub fn report_text(reports: &[Report]) -> String {
+    let mut text = String::new();
+    for report in reports {
+        push_report(&mut text, report);
+    }
+    let failed = reports.iter().filter(|r| r.failed()).count();
+    text.push_str(&format!("\n{} passed, {} failed\n", reports.len() - failed, failed));
+    text
+}
+
+// A single plain iteration reads like report_text; otherwise each iteration is listed with its
+// data, followed by a table of statuses and timings per iteration.
+pub fn summary_text(iterations: &[Iteration]) -> String {
+    if let [iteration] = iterations
+        && iteration.row.is_empty()
+    {
+        return report_text(&iteration.reports);
+    }
+    let mut text = String::new();
+    for iteration in iterations {
+        let row: Vec<String> = iteration.row.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+        text.push_str(&format!("Iteration {}", iteration.number));
+        if !row.is_empty() {
+            text.push_str(&format!(": {}", row.join(", ")));
+        }
+        text.push('\n');
+        for report in &iteration.reports {
+            push_report(&mut text, report);
+        }
+        text.push('\n');
+    }
+
+    text.push_str(&format!(
+        "{:>4}  {:>6}  {:>6}  {:>8}  {:>7}  statuses\n",
+        "#", "passed", "failed", "total ms", "avg ms"
+    ));
+    for iteration in iterations {
+        let total: u128 = iteration.reports.iter().map(|r| r.duration_ms).sum();
+        let count = iteration.reports.len().max(1) as u128;
+        let statuses: Vec<String> = iteration
+            .reports
+            .iter()
+            .map(|r| r.status.map_or("-".to_string(), |s| s.to_string()))
+            .collect();
+        text.push_str(&format!(
+            "{:>4}  {:>6}  {:>6}  {:>8}  {:>7}  {}\n",
+            iteration.number,
+            iteration.reports.len() - iteration.failed(),
+            iteration.failed(),
+            total,
+            total / count,
+            statuses.join(" ")
+        ));
+    }
+    let requests: usize = iterations.iter().map(|i| i.reports.len()).sum();
+    let failed: usize = iterations.iter().map(Iteration::failed).sum();
+    text.push_str(&format!(
+        "\n{} requests in {} iteration{}: {} passed, {} failed\n",
+        requests,
+        iterations.len(),
+        if iterations.len() == 1 { "" } else { "s" },
+        requests - failed,
+        failed
+    ));
+    text
+}
+
+// What a background run changed in its copy of the environment and tokens; None removes a
+// variable.
+#[derive(Debug, Clone, Default)]
+pub struct Changes {
+    pub environment: String,
+    pub variables: BTreeMap<String, Option<String>>,
+    pub captured: BTreeMap<String, Option<String>>,
+    pub tokens: HashMap<String, Token>,
+}
+
+fn changed(
+    before: &BTreeMap<String, String>,
+    after: &BTreeMap<String, String>,
+) -> BTreeMap<String, Option<String>> {
+    let removed = before.keys().filter(|name| !after.contains_key(*name)).map(|name| (name.clone(), None));
+    let set = after
+        .iter()
+        .filter(|(name, value)| before.get(*name) != Some(value))
+        .map(|(name, value)| (name.clone(), Some(value.clone())));
+    removed.chain(set).collect()
+}
+
+impl Changes {
+    fn between(
+        before: Option<Environment>,
+        after: Option<Environment>,
+        tokens_before: &HashMap<String, Token>,
+        tokens_after: HashMap<String, Token>,
+    ) -> Self {
+        // A run that had no environment uses `default`, the one the TUI would start too.
+        let after = after.or_else(|| before.clone()).unwrap_or_default();
+        let before = before.unwrap_or_default();
+        Changes {
+            environment: if after.name.is_empty() { "default".to_string() } else { after.name.clone() },
+            variables: changed(&before.variables, &after.variables),
+            captured: changed(&before.captured, &after.captured),
+            tokens: tokens_after
+                .into_iter()
+                .filter(|(key, token)| {
+                    tokens_before.get(key).is_none_or(|old| old.access_token != token.access_token)
+                })
+                .collect(),
+        }
+    }
+
+    pub fn has_variables(&self) -> bool {
+        !self.variables.is_empty() || !self.captured.is_empty()
+    }
+}
+
+pub enum Event {
+    Progress(Iteration),
+    Finished {
+        result: Result<(), String>,
+        changes: Changes,
+    },
+}
+
+// A run in the background, so the TUI stays responsive through delays and slow requests.
+pub struct Background {
+    pub name: String,
+    pub iterations: Vec<Iteration>,
+    receiver: Receiver<Event>,
+    stop: Arc<AtomicBool>,
+}
+
+impl Background {
+    // The worker gets its own App with copies of the caller's settings, environment and tokens.
+    // It saves nothing itself; its changes come back with Event::Finished.
+    pub fn start(app: &App, name: &str, options: Options) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let config = app.config.clone();
+        let environment = app.active_environment.clone();
+        let tokens = app.tokens.clone();
+        let worker_stop = stop.clone();
+        let collection = name.to_string();
+        std::thread::spawn(move || {
+            let mut app = App::new();
+            app.config = config;
+            app.active_environment = environment.clone();
+            app.tokens = tokens.clone();
+            app.detached = true;
+            let progress_sender = sender.clone();
+            let result = run(&mut app, &collection, &options, &worker_stop, &mut |iteration| {
+                let _ = progress_sender.send(Event::Progress(iteration.clone()));
+            });
+            let _ = sender.send(Event::Finished {
+                result: result.map(|_| ()).map_err(|e| e.to_string()),
+                changes: Changes::between(environment, app.active_environment, &tokens, app.tokens),
+            });
+        });
+        Background { name: name.to_string(), iterations: Vec::new(), receiver, stop }
+    }
+
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    // Applies progress to `iterations`; returns the final event once the run is over.
+    pub fn poll(&mut self) -> Option<Event> {
+        while let Ok(event) = self.receiver.try_recv() {
+            match event {
+                Event::Progress(iteration) => match self.iterations.last_mut() {
+                    Some(last) if last.number == iteration.number => *last = iteration,
+                    _ => self.iterations.push(iteration),
+                },
+                finished => return Some(finished),
+            }
+        }
+        None
+    }
+}
+
+// Characters XML 1.0 doesn't allow at all, such as ESC in server error text, become U+FFFD.
+fn escape_xml(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '\t' | '\n' | '\r' => c,
+            '\u{0}'..='\u{1f}' | '\u{fffe}' | '\u{ffff}' => '\u{fffd}',
+            c => c,
+        })
+        .collect::<String>()
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// One testcase per request and iteration; failed assertions and send errors become <failure>
+// elements.
+pub fn junit_xml(suite: &str, iterations: &[Iteration]) -> String {
+    let numbered = iterations.len() > 1;
+    let reports: Vec<Report> = iterations
+        .iter()
+        .flat_map(|iteration| {
+            iteration.reports.iter().cloned().map(move |mut report| {
+                if numbered {
+                    report.name = format!("{} #{}", report.name, iteration.number);
+                }
+                report
+            })
+        })
+        .collect();
+    let failures = reports.iter().filter(|r| r.failed()).count();
+    let seconds = |ms: u128| format!("{:.3}", ms as f64 / 1000.0);
+    let total: u128 = reports.iter().map(|r| r.duration_ms).sum();
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" time=\"{}\">\n",
+        escape_xml(suite),
+        reports.len(),
+        failures,
+        seconds(total)
+    ));
+    for report in &reports {
+        xml.push_str(&format!(
+            "  <testcase name=\"{}\" classname=\"{}\" time=\"{}\"",
+            escape_xml(&report.name),
+            escape_xml(&format!("{} {}", report.method, report.template)),
+            seconds(report.duration_ms)
+        ));
+        if !report.failed() && report.logs.is_empty() {
+            xml.push_str("/>\n");
+            continue;
+        }
+        xml.push_str(">\n");
+        if let Some(err) = &report.error {
+            xml.push_str(&format!("    <failure message=\"{}\"/>\n", escape_xml(err)));
+        }
+        for (assertion, failure) in &report.results {
+            if let Some(reason) = failure {
+                xml.push_str(&format!(
+                    "    <failure message=\"{}\">{}</failure>\n",
+                    escape_xml(&format!("{}: {}", assertion, reason)),
+                    escape_xml(assertion)
+                ));
+            }
+        }
+        if !report.logs.is_empty() {
+            let logs = report.logs.join("\n");
+            xml.push_str(&format!("    <system-out>{}</system-out>\n", escape_xml(&logs)));
+        }
+        xml.push_str("  </testcase>\n");
+    }
+    xml.push_str("</testsuite>\n");
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_xml_replaces_illegal_characters() {
+        assert_eq!(escape_xml("a<b & \"c\"\tok\n"), "a&lt;b &amp; &quot;c&quot;\tok\n");
+        assert_eq!(escape_xml("\u{1b}[31merror\u{0}"), "\u{fffd}[31merror\u{fffd}");
+    }
+
+    #[test]
+    fn junit_uses_the_saved_url() {
+        let request = Request {
+            url: "https://api.example.com/items?key={{api_key}}".to_string(),
+            ..Request::default()
+        };
+        let mut report = Report::new("items", &request);
+        report.url = "https://api.example.com/items?key=s3cr3t".to_string();
+        report.error = Some("server said \u{1b}oops".to_string());
+        let iteration = Iteration { number: 1, reports: vec![report], ..Iteration::default() };
+
+        let xml = junit_xml("suite", &[iteration]);
+        assert!(xml.contains("classname=\"GET https://api.example.com/items?key={{api_key}}\""));
+        assert!(!xml.contains("s3cr3t"));
+        assert!(xml.contains("<failure message=\"server said \u{fffd}oops\"/>"));
+    }
+
+    #[test]
+    fn request_errors_leave_out_the_url() {
+        let err = reqwest::blocking::get("http://127.0.0.1:1/items?key=s3cr3t").unwrap_err();
+        assert!(err.to_string().contains("s3cr3t"));
+        let mut report = Report::new("items", &Request::default());
+        report.error = Some(failure(Box::new(err)));
+        let iteration = Iteration { number: 1, reports: vec![report], ..Iteration::default() };
+        let xml = junit_xml("suite", &[iteration]);
+        assert!(xml.contains("<failure message=\"error sending request"), "{}", xml);
+        assert!(!xml.contains("s3cr3t"), "{}", xml);
+
+        let err = reqwest::blocking::get("http://127.0.0.1:1/items?key=s3cr3t").unwrap_err();
+        let message = failure(Box::new(std::io::Error::other(err)));
+        assert!(!message.contains("s3cr3t"), "{}", message);
+    }
+}
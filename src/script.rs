@@ -0,0 +1,215 @@
+use crate::http::{Header, Method, Request, Response};
+use crate::sigv4::{hex, hmac};
+use base64::Engine as _;
+use rhai::{Dynamic, Engine, Map, Scope};
+use sha2::{Digest, Sha256};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+// Stops runaway loops instead of hanging the TUI.
+const MAX_OPERATIONS: u64 = 10_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hook {
+    Pre,
+    Post,
+}
+
+impl Hook {
+    pub const ALL: [Hook; 2] = [Hook::Pre, Hook::Post];
+
+    // Also the file name of a folder's scripts: `pre.rhai` and `post.rhai`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Hook::Pre => "pre",
+            Hook::Post => "post",
+        }
+    }
+}
+
+impl std::str::FromStr for Hook {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "pre" => Ok(Hook::Pre),
+            "post" => Ok(Hook::Post),
+            _ => Err(format!("expected pre or post, got: {}", value)),
+        }
+    }
+}
+
+// What scripts reported through log()/print() and assert().
+#[derive(Debug, Clone, Default)]
+pub struct Output {
+    pub logs: Vec<String>,
+    pub assertions: Vec<(String, Option<String>)>,
+}
+
+impl Output {
+    pub fn is_empty(&self) -> bool {
+        self.logs.is_empty() && self.assertions.is_empty()
+    }
+
+    pub fn failures(&self) -> usize {
+        self.assertions.iter().filter(|(_, failure)| failure.is_some()).count()
+    }
+}
+
+// A script's name (shown in errors) and source.
+pub type Script = (String, String);
+
+fn engine(output: &Rc<RefCell<Output>>) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+
+    let logs = output.clone();
+    engine.on_print(move |text| logs.borrow_mut().logs.push(text.to_string()));
+    let logs = output.clone();
+    engine.register_fn("log", move |value: Dynamic| logs.borrow_mut().logs.push(value.to_string()));
+    let assertions = output.clone();
+    engine.register_fn("assert", move |passed: bool, message: &str| {
+        let failure = (!passed).then(|| "assertion failed".to_string());
+        assertions.borrow_mut().assertions.push((message.to_string(), failure));
+    });
+
+    // Enough to compute signatures and timestamps without leaving the script.
+    engine.register_fn("sha256", |text: &str| hex(&Sha256::digest(text.as_bytes())));
+    engine.register_fn("hmac_sha256", |key: &str, text: &str| hex(&hmac(key.as_bytes(), text)));
+    engine.register_fn("base64", |text: &str| {
+        base64::engine::general_purpose::STANDARD.encode(text)
+    });
+    engine.register_fn("unix_time", || chrono::Utc::now().timestamp());
+    engine.register_fn("iso_time", || {
+        chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+    });
+    engine
+}
+
+fn text(value: Dynamic) -> String {
+    if value.is_string() {
+        value.into_string().unwrap_or_default()
+    } else {
+        value.to_string()
+    }
+}
+
+fn vars_map(vars: &BTreeMap<String, String>) -> Map {
+    vars.iter()
+        .map(|(name, value)| (name.as_str().into(), value.clone().into()))
+        .collect()
+}
+
+fn headers_map<'a>(headers: impl Iterator<Item = (&'a str, &'a str)>) -> Map {
+    headers
+        .map(|(name, value)| (name.into(), value.to_string().into()))
+        .collect()
+}
+
+fn run(
+    scripts: &[Script],
+    scope: &mut Scope,
+    output: &Rc<RefCell<Output>>,
+) -> Result<(), String> {
+    let engine = engine(output);
+    for (name, source) in scripts {
+        engine
+            .run_with_scope(scope, source)
+            .map_err(|e| format!("{}: {}", name, e))?;
+    }
+    Ok(())
+}
+
+fn take_vars(scope: &Scope, vars: &mut BTreeMap<String, String>) -> Result<(), String> {
+    let map: Map = scope
+        .get_value("vars")
+        .ok_or("`vars` must stay a map")?;
+    *vars = map.into_iter().map(|(name, value)| (name.to_string(), text(value))).collect();
+    Ok(())
+}
+
+// Scripts see `request` (method, url, headers, body) and `vars`, and may change both.
+pub fn pre_request(
+    scripts: &[Script],
+    request: &mut Request,
+    vars: &mut BTreeMap<String, String>,
+) -> Result<Output, String> {
+    let enabled = || request.headers.iter().filter(|h| h.enabled).map(|h| (h.name.as_str(), h.value.as_str()));
+    let headers = headers_map(enabled());
+    let mut map = Map::new();
+    map.insert("method".into(), request.method.to_string().into());
+    map.insert("url".into(), request.url.clone().into());
+    map.insert("headers".into(), headers.clone().into());
+    map.insert("body".into(), request.body.clone().into());
+
+    let mut scope = Scope::new();
+    scope.push("request", map);
+    scope.push("vars", vars_map(vars));
+    let output = Rc::new(RefCell::new(Output::default()));
+    run(scripts, &mut scope, &output)?;
+
+    let mut map: Map = scope.get_value("request").ok_or("`request` must stay a map")?;
+    let field = |map: &mut Map, name: &str| map.remove(name).map(text).unwrap_or_default();
+    let method = field(&mut map, "method").to_uppercase();
+    request.method = *Method::ALL
+        .iter()
+        .find(|m| m.to_string() == method)
+        .ok_or_else(|| format!("script set an unknown method: {}", method))?;
+    request.url = field(&mut map, "url");
+    request.body = field(&mut map, "body");
+    // Headers are only rebuilt when changed, keeping their order and duplicates otherwise.
+    let new_headers: Map = map
+        .remove("headers")
+        .and_then(|h| h.try_cast())
+        .ok_or("`request.headers` must stay a map")?;
+    let changed = new_headers.len() != headers.len()
+        || new_headers
+            .iter()
+            .any(|(name, value)| headers.get(name).is_none_or(|old| old.to_string() != value.to_string()));
+    if changed {
+        request.headers.retain(|h| !h.enabled);
+        for (name, value) in new_headers {
+            request.headers.push(Header {
+                name: name.to_string(),
+                value: text(value),
+                enabled: true,
+            });
+        }
+    }
+    take_vars(&scope, vars)?;
+    Ok(output.take())
+}
+
+// Scripts see `request`, `response` (status, status_text, headers, body, time) and `vars`;
+// only `vars` changes are kept.
+pub fn post_response(
+    scripts: &[Script],
+    request: &Request,
+    response: &Response,
+    vars: &mut BTreeMap<String, String>,
+) -> Result<Output, String> {
+    let mut req = Map::new();
+    req.insert("method".into(), request.method.to_string().into());
+    req.insert("url".into(), request.url.clone().into());
+    let headers = request.headers.iter().filter(|h| h.enabled);
+    req.insert("headers".into(), headers_map(headers.map(|h| (h.name.as_str(), h.value.as_str()))).into());
+    req.insert("body".into(), request.body.clone().into());
+
+    let mut resp = Map::new();
+    resp.insert("status".into(), (response.status as i64).into());
+    resp.insert("status_text".into(), response.status_text.clone().into());
+    let headers = response.headers.iter().map(|(k, v)| (k.as_str(), v.as_str()));
+    resp.insert("headers".into(), headers_map(headers).into());
+    resp.insert("body".into(), response.body.clone().into());
+    resp.insert("time".into(), (response.duration_ms as i64).into());
+
+    let mut scope = Scope::new();
+    scope.push("request", req);
+    scope.push("response", resp);
+    scope.push("vars", vars_map(vars));
+    let output = Rc::new(RefCell::new(Output::default()));
+    run(scripts, &mut scope, &output)?;
+    take_vars(&scope, vars)?;
+    Ok(output.take())
+}
@@ -0,0 +1,220 @@
+use crate::http::{self, Request, Response};
+use std::io::Read;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+
+const CHUNK_SIZE: usize = 8192;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SseEvent {
+    pub event: Option<String>,
+    pub data: String,
+    pub id: Option<String>,
+}
+
+pub enum StreamMessage {
+    Started(Response),
+    Text(String),
+    Event(SseEvent),
+    Failed(String),
+    Finished,
+}
+
+// Splits a `text/event-stream` body into events as lines arrive.
+#[derive(Default)]
+struct SseParser {
+    line: String,
+    data: Vec<String>,
+    event: Option<String>,
+    id: Option<String>,
+    after_cr: bool,
+}
+
+impl SseParser {
+    fn feed(&mut self, text: &str) -> Vec<SseEvent> {
+        let mut events = Vec::new();
+        for c in text.chars() {
+            // CRLF, CR and LF all end a line.
+            if self.after_cr && c == '\n' {
+                self.after_cr = false;
+                continue;
+            }
+            self.after_cr = c == '\r';
+            if c == '\r' || c == '\n' {
+                let line = std::mem::take(&mut self.line);
+                events.extend(self.process_line(&line));
+            } else {
+                self.line.push(c);
+            }
+        }
+        events
+    }
+
+    fn process_line(&mut self, line: &str) -> Option<SseEvent> {
+        if line.is_empty() {
+            let event = self.event.take();
+            if self.data.is_empty() {
+                return None;
+            }
+            return Some(SseEvent {
+                event,
+                data: std::mem::take(&mut self.data).join("\n"),
+                id: self.id.clone(),
+            });
+        }
+        if line.starts_with(':') {
+            return None;
+        }
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            "data" => self.data.push(value.to_string()),
+            "event" => self.event = Some(value.to_string()),
+            "id" if !value.contains('\0') => self.id = Some(value.to_string()),
+            _ => {}
+        }
+        None
+    }
+}
+
+// Decodes UTF-8 across chunk boundaries, holding back an incomplete trailing character.
+fn decode_chunk(pending: &mut Vec<u8>, chunk: &[u8]) -> String {
+    pending.extend_from_slice(chunk);
+    let valid = match std::str::from_utf8(pending) {
+        Ok(_) => pending.len(),
+        Err(e) if e.error_len().is_none() => e.valid_up_to(),
+        Err(_) => pending.len(),
+    };
+    let rest = pending.split_off(valid);
+    let text = String::from_utf8_lossy(pending).into_owned();
+    *pending = rest;
+    text
+}
+
+pub struct Stream {
+    receiver: Receiver<StreamMessage>,
+    stop: Arc<AtomicBool>,
+}
+
+impl Stream {
+    pub fn start(request: Request) -> Stream {
+        Stream::spawn(move |sender, stop| read_stream(&request, sender, stop))
+    }
+
+    // Runs `read` on its own thread; it reports through the sender and should
+    // return early once the flag is set.
+    pub fn spawn<F>(read: F) -> Stream
+    where
+        F: FnOnce(&Sender<StreamMessage>, &AtomicBool) -> Result<(), Box<dyn std::error::Error>>
+            + Send
+            + 'static,
+    {
+        let (sender, receiver) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        thread::spawn(move || {
+            let message = match read(&sender, &stopped) {
+                Ok(()) => StreamMessage::Finished,
+                Err(err) => StreamMessage::Failed(err.to_string()),
+            };
+            let _ = sender.send(message);
+        });
+        Stream { receiver, stop }
+    }
+
+    pub fn messages(&self) -> Vec<StreamMessage> {
+        self.receiver.try_iter().collect()
+    }
+
+    // The reader notices once its current read returns; until then the
+    // connection stays open but nothing more is delivered.
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+fn read_stream(
+    request: &Request,
+    sender: &Sender<StreamMessage>,
+    stop: &AtomicBool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (head, mut resp) = http::open_stream(request)?;
+    let is_sse = head
+        .header("content-type")
+        .is_some_and(|ct| ct.to_ascii_lowercase().starts_with("text/event-stream"));
+    sender.send(StreamMessage::Started(head))?;
+
+    let mut parser = SseParser::default();
+    let mut pending = Vec::new();
+    let mut buf = [0u8; CHUNK_SIZE];
+    while !stop.load(Ordering::Relaxed) {
+        let read = resp.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        let text = decode_chunk(&mut pending, &buf[..read]);
+        if is_sse {
+            for event in parser.feed(&text) {
+                sender.send(StreamMessage::Event(event))?;
+            }
+        } else if !text.is_empty() {
+            sender.send(StreamMessage::Text(text))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(event: Option<&str>, data: &str, id: Option<&str>) -> SseEvent {
+        SseEvent {
+            event: event.map(str::to_string),
+            data: data.to_string(),
+            id: id.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn events_end_at_a_blank_line() {
+        let mut parser = SseParser::default();
+        let events = parser.feed(": comment\nevent: update\nid: 7\ndata: a\ndata:b\n\ndata: c\n\n");
+        assert_eq!(
+            events,
+            vec![event(Some("update"), "a\nb", Some("7")), event(None, "c", Some("7"))]
+        );
+    }
+
+    #[test]
+    fn events_span_chunks_and_line_endings() {
+        let mut parser = SseParser::default();
+        assert!(parser.feed("data: hel").is_empty());
+        assert!(parser.feed("lo\r").is_empty());
+        assert_eq!(parser.feed("\n\r\n"), vec![event(None, "hello", None)]);
+        assert_eq!(parser.feed("data: x\r\r"), vec![event(None, "x", None)]);
+    }
+
+    #[test]
+    fn blocks_without_data_are_dropped() {
+        let mut parser = SseParser::default();
+        assert!(parser.feed("event: ping\n\n").is_empty());
+        // The dropped block's event name does not leak into the next one.
+        assert_eq!(parser.feed("data\n\n"), vec![event(None, "", None)]);
+        assert!(parser.feed("id: a\0b\nretry: 10\n\n").is_empty());
+    }
+
+    #[test]
+    fn utf8_split_across_chunks_is_held_back() {
+        let bytes = "é!".as_bytes();
+        let mut pending = Vec::new();
+        assert_eq!(decode_chunk(&mut pending, &bytes[..1]), "");
+        assert_eq!(decode_chunk(&mut pending, &bytes[1..]), "é!");
+        assert!(pending.is_empty());
+        assert_eq!(decode_chunk(&mut pending, &[0xff, b'a']), "\u{fffd}a");
+    }
+}
@@ -0,0 +1,301 @@
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tower_layer::Layer;
+use tracing::field::{Field, Visit};
+use tracing::instrument::WithSubscriber;
+use tracing::{Event, Metadata, Subscriber, span};
+use tower_service::Service;
+
+use crate::util::format_size;
+
+const BAR_WIDTH: usize = 40;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Timing {
+    pub dns_ms: f64,
+    #[serde(alias = "connect_ms")]
+    pub tcp_ms: f64,
+    pub tls_ms: f64,
+    pub ttfb_ms: f64,
+    pub download_ms: f64,
+    pub total_ms: f64,
+    pub request_bytes: usize,
+    pub response_header_bytes: usize,
+    pub response_body_bytes: usize,
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+impl Timing {
+    pub fn phases(&self) -> [(&'static str, f64); 5] {
+        [
+            ("DNS lookup", self.dns_ms),
+            ("TCP connect", self.tcp_ms),
+            ("TLS handshake", self.tls_ms),
+            ("Waiting (TTFB)", self.ttfb_ms),
+            ("Download", self.download_ms),
+        ]
+    }
+
+    pub fn waterfall(&self) -> String {
+        let total: f64 = self.phases().iter().map(|(_, ms)| ms).sum();
+        let scale = if total > 0.0 { BAR_WIDTH as f64 / total } else { 0.0 };
+
+        let mut lines = Vec::new();
+        let mut elapsed = 0.0;
+        for (name, ms) in self.phases() {
+            let mut from = ((elapsed * scale).round() as usize).min(BAR_WIDTH);
+            let mut to = (((elapsed + ms) * scale).round() as usize).min(BAR_WIDTH);
+            // Phases too short to fill a column still get one.
+            if ms > 0.0 && from == to {
+                if to < BAR_WIDTH { to += 1 } else { from -= 1 }
+            }
+            lines.push(format!(
+                "{:<15}{:>10.1} ms  |{}{}{}|",
+                name,
+                ms,
+                " ".repeat(from),
+                "█".repeat(to - from),
+                " ".repeat(BAR_WIDTH - to),
+            ));
+            elapsed += ms;
+        }
+        lines.push(format!("{:<15}{:>10.1} ms", "Total", self.total_ms));
+        lines.push(String::new());
+        lines.push(format!(
+            "Request        {:>10}",
+            format_size(self.request_bytes)
+        ));
+        lines.push(format!(
+            "Response       {:>10}  (headers {}, body {})",
+            format_size(self.response_header_bytes + self.response_body_bytes),
+            format_size(self.response_header_bytes),
+            format_size(self.response_body_bytes)
+        ));
+        lines.join("\n")
+    }
+}
+
+#[derive(Default)]
+struct Marks {
+    start: Option<Instant>,
+    dns: Option<(Instant, Instant)>,
+    tcp: Option<Instant>,
+    connect: Option<(Instant, Instant)>,
+}
+
+// Collects phase timestamps from the client's resolver and connector while a request runs.
+#[derive(Clone, Default)]
+pub struct Recorder {
+    marks: Arc<Mutex<Marks>>,
+    tls: bool,
+}
+
+impl Recorder {
+    pub fn new(url: &str) -> Self {
+        Self {
+            marks: Arc::default(),
+            tls: url.get(..8).is_some_and(|scheme| scheme.eq_ignore_ascii_case("https://")),
+        }
+    }
+
+    pub fn start(&self) {
+        *self.marks.lock().unwrap() = Marks {
+            start: Some(Instant::now()),
+            ..Marks::default()
+        };
+    }
+
+    fn tcp_connected(&self) {
+        self.marks.lock().unwrap().tcp = Some(Instant::now());
+    }
+
+    fn connected(&self, started: Instant) {
+        self.marks.lock().unwrap().connect = Some((started, Instant::now()));
+    }
+
+    pub fn finish(&self, headers_at: Instant, done_at: Instant) -> Timing {
+        let marks = self.marks.lock().unwrap();
+        let start = marks.start.unwrap_or(headers_at);
+        let dns = marks.dns.map_or(Duration::ZERO, |(from, to)| to - from);
+        // The connector resolves the name itself, so DNS is part of the connect time.
+        let setup = marks
+            .connect
+            .map_or(Duration::ZERO, |(from, to)| to - from)
+            .saturating_sub(dns);
+        let (tcp, tls) = match (marks.connect, marks.tcp) {
+            (Some((from, _)), Some(tcp_at)) if self.tls => {
+                let tcp = (tcp_at.saturating_duration_since(from)).saturating_sub(dns).min(setup);
+                (tcp, setup - tcp)
+            }
+            _ => (setup, Duration::ZERO),
+        };
+        // A reused connection starts waiting as soon as the request is sent.
+        let waiting_from = marks.connect.map_or(start, |(_, ready)| ready);
+
+        Timing {
+            dns_ms: millis(dns),
+            tcp_ms: millis(tcp),
+            tls_ms: millis(tls),
+            ttfb_ms: millis(headers_at.saturating_duration_since(waiting_from)),
+            download_ms: millis(done_at.saturating_duration_since(headers_at)),
+            total_ms: millis(done_at.saturating_duration_since(start)),
+            ..Timing::default()
+        }
+    }
+}
+
+impl Resolve for Recorder {
+    fn resolve(&self, name: Name) -> Resolving {
+        let marks = self.marks.clone();
+        Box::pin(async move {
+            let started = Instant::now();
+            // getaddrinfo blocks, so it runs off the client's runtime like reqwest's own resolver.
+            let host = name.as_str().to_string();
+            let addrs: Vec<SocketAddr> = tokio::task::spawn_blocking(move || (host.as_str(), 0).to_socket_addrs())
+                .await
+                .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send + Sync>)?
+                .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send + Sync>)?
+                .collect();
+            marks.lock().unwrap().dns = Some((started, Instant::now()));
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+impl<S> Layer<S> for Recorder {
+    type Service = ConnectTimer<S>;
+
+    fn layer(&self, inner: S) -> ConnectTimer<S> {
+        ConnectTimer {
+            inner,
+            recorder: self.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ConnectTimer<S> {
+    inner: S,
+    recorder: Recorder,
+}
+
+impl<S, R> Service<R> for ConnectTimer<S>
+where
+    S: Service<R>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: R) -> Self::Future {
+        let recorder = self.recorder.clone();
+        let started = Instant::now();
+        let watch = TcpWatch { recorder: recorder.clone() };
+        let connecting = self.inner.call(req).with_subscriber(watch);
+        Box::pin(async move {
+            let conn = connecting.await?;
+            recorder.connected(started);
+            Ok(conn)
+        })
+    }
+}
+
+// hyper's HttpConnector logs when the TCP connection is up, before reqwest starts the TLS
+// handshake on it; listening for that event while the connector runs splits the two.
+struct TcpWatch {
+    recorder: Recorder,
+}
+
+#[derive(Default)]
+struct Message(String);
+
+impl Visit for Message {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            self.0 = format!("{:?}", value);
+        }
+    }
+}
+
+impl Subscriber for TcpWatch {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.is_event() && metadata.target().starts_with("hyper_util::client::legacy::connect")
+    }
+
+    fn new_span(&self, _: &span::Attributes<'_>) -> span::Id {
+        span::Id::from_u64(1)
+    }
+
+    fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+
+    fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut message = Message::default();
+        event.record(&mut message);
+        if message.0.starts_with("connected to") {
+            self.recorder.tcp_connected();
+        }
+    }
+
+    fn enter(&self, _: &span::Id) {}
+
+    fn exit(&self, _: &span::Id) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    #[test]
+    fn connector_reports_when_tcp_is_up() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let _ = stream.read(&mut [0; 1024]);
+            let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n");
+        });
+
+        let recorder = Recorder::new(&url);
+        let client = reqwest::blocking::Client::builder()
+            .connector_layer(recorder.clone())
+            .build()
+            .unwrap();
+        recorder.start();
+        client.get(&url).send().unwrap();
+
+        let marks = recorder.marks.lock().unwrap();
+        let (started, ready) = marks.connect.unwrap();
+        let tcp = marks.tcp.unwrap();
+        assert!(started <= tcp && tcp <= ready);
+    }
+
+    #[test]
+    fn plain_http_has_no_tls_phase() {
+        let recorder = Recorder::new("http://localhost/");
+        let started = Instant::now();
+        recorder.marks.lock().unwrap().tcp = Some(started);
+        recorder.connected(started);
+        let timing = recorder.finish(Instant::now(), Instant::now());
+        assert_eq!(timing.tls_ms, 0.0);
+        assert!(Recorder::new("HTTPS://localhost/").tls);
+    }
+}
@@ -1,9 +1,15 @@
-use crate::app::{App, Mode, Panel};
+use crate::app::{App, MessageKind, Mode, Panel, ResponseView};
+use crate::buffer;
+use crate::http::{BodyType, Response};
+use crate::grpc;
+use crate::util::format_size;
+use crate::websocket;
+use std::rc::Rc;
 use ratatui::{
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span, Text},
-    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph},
     Frame,
 };
 
@@ -19,6 +25,51 @@ pub fn render(f: &mut Frame, app: &mut App) {
     render_main_content(f, app, chunks[0]);
     render_status_line(f, app, chunks[1]);
     set_cursor(f, app);
+    render_completion_popup(f, app);
+}
+
+fn render_completion_popup(f: &mut Frame, app: &App) {
+    let Some(completion) = &app.insert_completion else {
+        return;
+    };
+    let Some((x, y)) = calculate_cursor_position(f, app) else {
+        return;
+    };
+
+    let area = f.area();
+    let max_rows = 8;
+    let first = completion.index.saturating_sub(max_rows - 1);
+    let items: Vec<ListItem> = completion
+        .candidates
+        .iter()
+        .skip(first)
+        .take(max_rows)
+        .map(|c| ListItem::new(c.as_str()))
+        .collect();
+
+    let width = completion
+        .candidates
+        .iter()
+        .map(|c| buffer::display_width(c))
+        .max()
+        .unwrap_or(0) as u16
+        + 2;
+    let height = items.len() as u16 + 2;
+    let popup = Rect {
+        x: x.min(area.right().saturating_sub(width)),
+        y: if y + 1 + height <= area.bottom() { y + 1 } else { y.saturating_sub(height) },
+        width: width.min(area.width),
+        height: height.min(area.height),
+    };
+
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL))
+        .highlight_style(Style::default().bg(Color::Cyan).fg(Color::Black));
+    let mut state = ListState::default();
+    state.select(Some(completion.index - first));
+
+    f.render_widget(Clear, popup);
+    f.render_stateful_widget(list, popup, &mut state);
 }
 
 fn render_main_content(f: &mut Frame, app: &mut App, area: Rect) {
@@ -51,6 +102,9 @@ fn render_sidebar(f: &mut Frame, app: &App, area: Rect) {
             let display_name = name.strip_suffix(".json").unwrap_or(name);
             ListItem::new(display_name)
         })
+        .chain(app.grpc_methods.iter().map(|method| {
+            ListItem::new(format!("rpc {}", method)).style(Style::default().fg(Color::Magenta))
+        }))
         .collect();
 
     let list = List::new(items)
@@ -75,19 +129,31 @@ fn render_sidebar(f: &mut Frame, app: &App, area: Rect) {
     f.render_stateful_widget(list, area, &mut list_state);
 }
 
-fn render_request_builder(f: &mut Frame, app: &mut App, area: Rect) {
-    let chunks = Layout::default()
+// GraphQL bodies get a Variables editor below the query.
+fn request_builder_layout(area: Rect, app: &App) -> Rc<[Rect]> {
+    let variables = if app.current_request.body_type == BodyType::GraphQL { 8 } else { 0 };
+    Layout::default()
         .direction(Direction::Vertical)
         .constraints([
             Constraint::Length(3),
+            Constraint::Length(6),
             Constraint::Length(8),
             Constraint::Min(0),
+            Constraint::Length(variables),
         ])
-        .split(area);
+        .split(area)
+}
+
+fn render_request_builder(f: &mut Frame, app: &mut App, area: Rect) {
+    let chunks = request_builder_layout(area, app);
 
     render_url_input(f, app, chunks[0]);
-    render_headers_table(f, app, chunks[1]);
-    render_body_input(f, app, chunks[2]);
+    render_params_table(f, app, chunks[1]);
+    render_headers_table(f, app, chunks[2]);
+    render_body_input(f, app, chunks[3]);
+    if app.current_request.body_type == BodyType::GraphQL {
+        render_variables_input(f, app, chunks[4]);
+    }
 }
 
 fn render_url_input(f: &mut Frame, app: &App, area: Rect) {
@@ -98,7 +164,20 @@ fn render_url_input(f: &mut Frame, app: &App, area: Rect) {
         Style::default()
     };
 
-    let title = format!("URL({})", app.current_request.method);
+    let method = if grpc::is_grpc_url(&app.url_buffer) {
+        "gRPC".to_string()
+    } else if !websocket::is_websocket_url(&app.url_buffer) {
+        app.current_request.method.to_string()
+    } else if app.websocket.is_some() {
+        "WS connected".to_string()
+    } else {
+        "WS".to_string()
+    };
+    let title = if app.current_request.auth.is_none() {
+        format!("URL({})", method)
+    } else {
+        format!("URL({}) [{}]", method, app.current_request.auth.kind())
+    };
     let url_widget = Paragraph::new(app.url_buffer.as_str())
         .block(
             Block::default()
@@ -110,6 +189,48 @@ fn render_url_input(f: &mut Frame, app: &App, area: Rect) {
     f.render_widget(url_widget, area);
 }
 
+fn render_params_table(f: &mut Frame, app: &mut App, area: Rect) {
+    let is_focused = app.active_panel == Panel::Params;
+    let border_style = if is_focused {
+        Style::default().fg(Color::Cyan)
+    } else {
+        Style::default()
+    };
+
+    let visible_rows = area.height.saturating_sub(2);
+    let cursor_line = {
+        let cursor = *app.cursors.get(&Panel::Params).unwrap_or(&0);
+        let (line, _) = app.cursor_to_line_col(&app.params_buffer, cursor);
+        line as u16
+    };
+    app.update_scroll(Panel::Params, cursor_line, visible_rows);
+    let scroll = app.scroll_offset(Panel::Params);
+
+    let params_widget = Paragraph::new(dim_disabled_lines(&app.params_buffer))
+        .scroll((scroll, 0))
+        .block(
+            Block::default()
+                .title("Params")
+                .borders(Borders::ALL)
+                .border_style(border_style),
+        );
+
+    f.render_widget(params_widget, area);
+}
+
+fn dim_disabled_lines(text: &str) -> Text<'_> {
+    text.lines()
+        .map(|line| {
+            if line.trim_start().starts_with('#') {
+                Line::styled(line, Style::default().fg(Color::DarkGray))
+            } else {
+                Line::raw(line)
+            }
+        })
+        .collect::<Vec<_>>()
+        .into()
+}
+
 fn render_headers_table(f: &mut Frame, app: &mut App, area: Rect) {
     let is_focused = app.active_panel == Panel::Headers;
     let border_style = if is_focused {
@@ -126,7 +247,7 @@ fn render_headers_table(f: &mut Frame, app: &mut App, area: Rect) {
     app.update_scroll(Panel::Headers, cursor_line, visible_rows);
     let scroll = app.scroll_offset(Panel::Headers);
 
-    let headers_widget = Paragraph::new(app.headers_buffer.as_str())
+    let headers_widget = Paragraph::new(dim_disabled_lines(&app.headers_buffer))
         .scroll((scroll, 0))
         .block(
             Block::default()
@@ -154,11 +275,19 @@ fn render_body_input(f: &mut Frame, app: &mut App, area: Rect) {
     app.update_scroll(Panel::Body, cursor_line, visible_rows);
     let scroll = app.scroll_offset(Panel::Body);
 
-    let body_widget = Paragraph::new(app.body_buffer.as_str())
+    let body_text = match app.current_request.body_type {
+        BodyType::Form | BodyType::Multipart => dim_disabled_lines(&app.body_buffer),
+        _ => Text::raw(app.body_buffer.as_str()),
+    };
+    let body_widget = Paragraph::new(body_text)
         .scroll((scroll, 0))
         .block(
             Block::default()
-                .title("Body")
+                .title(if websocket::is_websocket_url(&app.url_buffer) || grpc::is_grpc_url(&app.url_buffer) {
+                    format!("Message({})", app.current_request.body_type)
+                } else {
+                    format!("Body({})", app.current_request.body_type)
+                })
                 .borders(Borders::ALL)
                 .border_style(border_style),
         );
@@ -166,6 +295,35 @@ fn render_body_input(f: &mut Frame, app: &mut App, area: Rect) {
     f.render_widget(body_widget, area);
 }
 
+fn render_variables_input(f: &mut Frame, app: &mut App, area: Rect) {
+    let is_focused = app.active_panel == Panel::Variables;
+    let border_style = if is_focused {
+        Style::default().fg(Color::Cyan)
+    } else {
+        Style::default()
+    };
+
+    let visible_rows = area.height.saturating_sub(2);
+    let cursor_line = {
+        let cursor = *app.cursors.get(&Panel::Variables).unwrap_or(&0);
+        let (line, _) = app.cursor_to_line_col(&app.variables_buffer, cursor);
+        line as u16
+    };
+    app.update_scroll(Panel::Variables, cursor_line, visible_rows);
+    let scroll = app.scroll_offset(Panel::Variables);
+
+    let variables_widget = Paragraph::new(app.variables_buffer.as_str())
+        .scroll((scroll, 0))
+        .block(
+            Block::default()
+                .title("Variables")
+                .borders(Borders::ALL)
+                .border_style(border_style),
+        );
+
+    f.render_widget(variables_widget, area);
+}
+
 fn render_response_viewer(f: &mut Frame, app: &mut App, area: Rect) {
     let is_focused = app.active_panel == Panel::Response;
     let border_style = if is_focused {
@@ -188,7 +346,7 @@ fn render_response_viewer(f: &mut Frame, app: &mut App, area: Rect) {
         if let Some(anchor) = app.visual_anchor {
             let cursor = *app.cursors.get(&Panel::Response).unwrap_or(&0);
             let start = anchor.min(cursor);
-            let end = (anchor.max(cursor) + 1).min(raw.len());
+            let end = buffer::next_boundary(raw, anchor.max(cursor));
             build_highlighted_text(raw, start, end)
         } else {
             Text::raw(raw.as_str())
@@ -201,7 +359,14 @@ fn render_response_viewer(f: &mut Frame, app: &mut App, area: Rect) {
         .scroll((scroll, 0))
         .block(
             Block::default()
-                .title("Response")
+                .title(match app.response_view {
+                    ResponseView::Body => "Response",
+                    ResponseView::Hex => "Response(Hex)",
+                    ResponseView::Timing => "Response(Timing)",
+                    ResponseView::Tests => "Response(Tests)",
+                    ResponseView::Environment => "Response(Environment)",
+                    ResponseView::Preview => "Response(Preview)",
+                })
                 .borders(Borders::ALL)
                 .border_style(border_style),
         );
@@ -210,6 +375,11 @@ fn render_response_viewer(f: &mut Frame, app: &mut App, area: Rect) {
 }
 
 fn render_status_line(f: &mut Frame, app: &App, area: Rect) {
+    let base_style = Style::default()
+        .bg(Color::DarkGray)
+        .add_modifier(Modifier::BOLD);
+    f.render_widget(Paragraph::new("").style(base_style), area);
+
     let mode_text = match app.mode {
         Mode::Normal => "-- NORMAL --",
         Mode::Insert => "-- INSERT --",
@@ -217,29 +387,107 @@ fn render_status_line(f: &mut Frame, app: &App, area: Rect) {
         Mode::Command => "",
     };
 
-    let content = if app.mode == Mode::Command {
-        format!(":{}", app.command_buffer)
+    let mut left = Vec::new();
+    if app.mode == Mode::Command {
+        left.push(Span::raw(format!(":{}", app.command_buffer)));
     } else {
-        mode_text.to_string()
-    };
+        left.push(Span::raw(mode_text));
+        if let Some(message) = app.current_message() {
+            let style = match message.kind {
+                MessageKind::Info => Style::default(),
+                MessageKind::Error => Style::default().fg(Color::LightRed),
+            };
+            left.push(Span::raw("  "));
+            left.push(Span::styled(message.text.as_str(), style));
+        }
+    }
 
-    let status_line = Paragraph::new(content)
-        .style(
-            Style::default()
-                .bg(Color::DarkGray)
-                .add_modifier(Modifier::BOLD),
+    let mut summary = app
+        .last_response
+        .as_ref()
+        .filter(|_| app.mode != Mode::Command)
+        .map(response_summary)
+        .unwrap_or_default();
+    if app.mode != Mode::Command
+        && let Some(oauth) = app.oauth_status()
+    {
+        summary.spans.insert(0, Span::raw(format!("{} | ", oauth)));
+    }
+    if app.mode != Mode::Command && app.websocket.is_some() {
+        summary.spans.insert(
+            0,
+            Span::styled("ws connected (Esc closes) | ", Style::default().fg(Color::LightGreen)),
+        );
+    }
+    if app.mode != Mode::Command && app.authorization.is_some() {
+        summary.spans.insert(
+            0,
+            Span::styled("authorizing (Esc cancels) | ", Style::default().fg(Color::LightGreen)),
+        );
+    }
+    if app.mode != Mode::Command && app.run.is_some() {
+        summary.spans.insert(
+            0,
+            Span::styled("running (Esc stops) | ", Style::default().fg(Color::LightGreen)),
+        );
+    }
+    if app.mode != Mode::Command && app.stream.is_some() {
+        summary.spans.insert(
+            0,
+            Span::styled("streaming (Esc stops) | ", Style::default().fg(Color::LightGreen)),
         );
+    }
+    let summary_width = summary.width() as u16;
+
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Min(0), Constraint::Length(summary_width)])
+        .split(area);
 
-    f.render_widget(status_line, area);
+    f.render_widget(Paragraph::new(Line::from(left)).style(base_style), chunks[0]);
+    f.render_widget(Paragraph::new(summary).style(base_style), chunks[1]);
+}
+
+fn response_summary(response: &Response) -> Line<'_> {
+    let status_color = match response.status {
+        200..=299 => Color::Green,
+        300..=399 => Color::Cyan,
+        400..=499 => Color::Yellow,
+        _ => Color::Red,
+    };
+    let separator = Span::raw(" | ");
+
+    let mut spans = vec![
+        Span::styled(response.status_text.as_str(), Style::default().fg(status_color)),
+        separator.clone(),
+        Span::raw(format!("{} ms", response.duration_ms)),
+        separator.clone(),
+        Span::raw(format_size(response.size())),
+    ];
+    if let Some(content_type) = response.header("content-type") {
+        spans.push(separator);
+        spans.push(Span::raw(content_type.split(';').next().unwrap_or(content_type)));
+    }
+    spans.push(Span::raw(" "));
+    Line::from(spans)
 }
 
 fn set_cursor(f: &mut Frame, app: &App) {
     if app.mode == Mode::Command {
+        let area = f.area();
+        let cursor = buffer::clamp(&app.command_buffer, app.command_cursor);
+        let x = 1 + buffer::display_width(&app.command_buffer[..cursor]) as u16;
+        f.set_cursor_position((area.x + x, area.bottom().saturating_sub(1)));
         return;
     }
 
     match app.active_panel {
-        Panel::Url | Panel::Headers | Panel::Body | Panel::Response => {
+        Panel::Url
+        | Panel::Params
+        | Panel::Headers
+        | Panel::Body
+        | Panel::Variables
+        | Panel::Response => {
             if let Some((x, y)) = calculate_cursor_position(f, app) {
                 f.set_cursor_position((x, y));
             }
@@ -268,32 +516,36 @@ fn calculate_cursor_position(f: &mut Frame, app: &App) -> Option<(u16, u16)> {
         ])
         .split(main_chunks[0]);
 
-    let request_builder_area = horizontal_chunks[1];
-
-    let vertical_chunks = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([
-            Constraint::Length(3),
-            Constraint::Length(8),
-            Constraint::Min(0),
-        ])
-        .split(request_builder_area);
+    let vertical_chunks = request_builder_layout(horizontal_chunks[1], app);
 
     match app.active_panel {
         Panel::Url => {
             let url_area = vertical_chunks[0];
-            let cursor = app.cursor();
-            let cursor_offset = cursor.min(app.url_buffer.len()) as u16;
+            let cursor = buffer::clamp(&app.url_buffer, app.cursor());
+            let cursor_offset = buffer::display_width(&app.url_buffer[..cursor]) as u16;
 
             Some((
                 url_area.x + 1 + cursor_offset,
                 url_area.y + 1,
             ))
         }
+        Panel::Params => {
+            let params_area = vertical_chunks[1];
+            let cursor = app.cursor();
+            let (line, col) = app.cursor_to_line_col(&app.params_buffer, cursor);
+            let col = line_display_col(&app.params_buffer, line, col);
+            let scroll = app.scroll_offset(Panel::Params);
+
+            Some((
+                params_area.x + 1 + col as u16,
+                params_area.y + 1 + line as u16 - scroll,
+            ))
+        }
         Panel::Headers => {
-            let headers_area = vertical_chunks[1];
+            let headers_area = vertical_chunks[2];
             let cursor = app.cursor();
             let (line, col) = app.cursor_to_line_col(&app.headers_buffer, cursor);
+            let col = line_display_col(&app.headers_buffer, line, col);
             let scroll = app.scroll_offset(Panel::Headers);
 
             Some((
@@ -302,9 +554,10 @@ fn calculate_cursor_position(f: &mut Frame, app: &App) -> Option<(u16, u16)> {
             ))
         }
         Panel::Body => {
-            let body_area = vertical_chunks[2];
+            let body_area = vertical_chunks[3];
             let cursor = app.cursor();
             let (line, col) = app.cursor_to_line_col(&app.body_buffer, cursor);
+            let col = line_display_col(&app.body_buffer, line, col);
             let scroll = app.scroll_offset(Panel::Body);
 
             Some((
@@ -312,11 +565,24 @@ fn calculate_cursor_position(f: &mut Frame, app: &App) -> Option<(u16, u16)> {
                 body_area.y + 1 + line as u16 - scroll,
             ))
         }
+        Panel::Variables => {
+            let variables_area = vertical_chunks[4];
+            let cursor = app.cursor();
+            let (line, col) = app.cursor_to_line_col(&app.variables_buffer, cursor);
+            let col = line_display_col(&app.variables_buffer, line, col);
+            let scroll = app.scroll_offset(Panel::Variables);
+
+            Some((
+                variables_area.x + 1 + col as u16,
+                variables_area.y + 1 + line as u16 - scroll,
+            ))
+        }
         Panel::Response => {
             let response_area = horizontal_chunks[2];
             let cursor = *app.cursors.get(&Panel::Response).unwrap_or(&0);
             let buffer = app.response_text();
             let (line, col) = app.cursor_to_line_col(buffer, cursor);
+            let col = line_display_col(buffer, line, col);
             let scroll = app.scroll_offset(Panel::Response);
 
             Some((
@@ -328,6 +594,13 @@ fn calculate_cursor_position(f: &mut Frame, app: &App) -> Option<(u16, u16)> {
     }
 }
 
+fn line_display_col(text: &str, line: usize, col: usize) -> usize {
+    text.lines()
+        .nth(line)
+        .and_then(|l| l.get(..col))
+        .map_or(0, buffer::display_width)
+}
+
 fn build_highlighted_text(raw: &str, start: usize, end: usize) -> Text<'_> {
     let normal_style = Style::default();
     let sel_style = Style::default()
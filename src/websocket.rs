@@ -0,0 +1,201 @@
+use crate::auth::{ApiKeyLocation, Auth};
+use crate::http::{Request, Response};
+use base64::Engine;
+use std::io::ErrorKind;
+use std::net::TcpStream;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+use std::time::Duration;
+use tungstenite::client::IntoClientRequest;
+use tungstenite::http::{HeaderName, HeaderValue};
+use tungstenite::protocol::CloseFrame;
+use tungstenite::stream::MaybeTlsStream;
+use tungstenite::{Message, WebSocket};
+
+// How long a read waits before the connection checks for frames to send.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+const PREVIEW_BYTES: usize = 32;
+
+pub fn is_websocket_url(url: &str) -> bool {
+    let url = url.trim_start().to_ascii_lowercase();
+    url.starts_with("ws://") || url.starts_with("wss://")
+}
+
+pub enum Outgoing {
+    Text(String),
+    Binary(Vec<u8>),
+    Ping(Vec<u8>),
+    Close(u16, String),
+}
+
+pub enum WsEvent {
+    Connected(Response),
+    Sent(Message),
+    Received(Message),
+    Closed,
+    Failed(String),
+}
+
+pub fn describe(message: &Message) -> String {
+    let preview = |bytes: &[u8]| {
+        let mut hex: Vec<String> = bytes
+            .iter()
+            .take(PREVIEW_BYTES)
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        if bytes.len() > PREVIEW_BYTES {
+            hex.push("...".to_string());
+        }
+        hex.join(" ")
+    };
+    match message {
+        Message::Text(text) => text.to_string(),
+        Message::Binary(bytes) => format!("binary {} bytes: {}", bytes.len(), preview(bytes)),
+        Message::Ping(bytes) if bytes.is_empty() => "ping".to_string(),
+        Message::Ping(bytes) => format!("ping {}", String::from_utf8_lossy(bytes)),
+        Message::Pong(bytes) if bytes.is_empty() => "pong".to_string(),
+        Message::Pong(bytes) => format!("pong {}", String::from_utf8_lossy(bytes)),
+        Message::Close(Some(frame)) => format!("close {} {}", u16::from(frame.code), frame.reason),
+        Message::Close(None) => "close".to_string(),
+        Message::Frame(frame) => format!("frame {} bytes", frame.len()),
+    }
+}
+
+// The handshake carries the request's headers and any auth that fits in a header or the query.
+fn handshake_request(
+    request: &Request,
+) -> Result<tungstenite::handshake::client::Request, Box<dyn std::error::Error>> {
+    let mut url = reqwest::Url::parse(request.url.trim())?;
+    if let Auth::ApiKey { name, value, location: ApiKeyLocation::Query } = &request.auth {
+        url.query_pairs_mut().append_pair(name, value);
+    }
+    let mut handshake = url.as_str().into_client_request()?;
+    let headers = handshake.headers_mut();
+    for header in request.headers.iter().filter(|h| h.enabled) {
+        headers.append(
+            HeaderName::from_bytes(header.name.as_bytes())?,
+            HeaderValue::from_str(&header.value)?,
+        );
+    }
+
+    let authorization = match &request.auth {
+        Auth::None | Auth::ApiKey { location: ApiKeyLocation::Query, .. } => None,
+        Auth::Basic { username, password } => Some(format!(
+            "Basic {}",
+            base64::engine::general_purpose::STANDARD.encode(format!("{}:{}", username, password))
+        )),
+        Auth::Bearer { token } => Some(format!("Bearer {}", token)),
+        Auth::ApiKey { name, value, location: ApiKeyLocation::Header } => {
+            headers.insert(HeaderName::from_bytes(name.as_bytes())?, HeaderValue::from_str(value)?);
+            None
+        }
+        auth => {
+            return Err(format!("{} auth is not supported for WebSocket", auth.kind()).into());
+        }
+    };
+    if let Some(authorization) = authorization {
+        headers.insert(tungstenite::http::header::AUTHORIZATION, HeaderValue::from_str(&authorization)?);
+    }
+    Ok(handshake)
+}
+
+fn set_read_timeout(socket: &WebSocket<MaybeTlsStream<TcpStream>>) -> std::io::Result<()> {
+    match socket.get_ref() {
+        MaybeTlsStream::Plain(stream) => stream.set_read_timeout(Some(POLL_INTERVAL)),
+        MaybeTlsStream::NativeTls(stream) => stream.get_ref().set_read_timeout(Some(POLL_INTERVAL)),
+        _ => Ok(()),
+    }
+}
+
+pub struct Connection {
+    outgoing: Sender<Outgoing>,
+    events: Receiver<WsEvent>,
+}
+
+impl Connection {
+    pub fn open(request: &Request) -> Result<Connection, Box<dyn std::error::Error>> {
+        let handshake = handshake_request(request)?;
+        let (outgoing, outgoing_rx) = mpsc::channel();
+        let (events_tx, events) = mpsc::channel();
+        thread::spawn(move || {
+            if let Err(err) = run(handshake, &outgoing_rx, &events_tx) {
+                let _ = events_tx.send(WsEvent::Failed(err.to_string()));
+            }
+        });
+        Ok(Connection { outgoing, events })
+    }
+
+    // Returns false once the connection has ended.
+    pub fn send(&self, message: Outgoing) -> bool {
+        self.outgoing.send(message).is_ok()
+    }
+
+    pub fn events(&self) -> Vec<WsEvent> {
+        self.events.try_iter().collect()
+    }
+}
+
+fn run(
+    handshake: tungstenite::handshake::client::Request,
+    outgoing: &Receiver<Outgoing>,
+    events: &Sender<WsEvent>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (mut socket, resp) = tungstenite::connect(handshake)?;
+    set_read_timeout(&socket)?;
+    events.send(WsEvent::Connected(Response {
+        status: resp.status().as_u16(),
+        status_text: resp.status().to_string(),
+        headers: resp
+            .headers()
+            .iter()
+            .map(|(k, v)| (k.to_string(), String::from_utf8_lossy(v.as_bytes()).to_string()))
+            .collect(),
+        ..Response::default()
+    }))?;
+
+    loop {
+        match outgoing.try_recv() {
+            Ok(out) => {
+                let message = match out {
+                    Outgoing::Text(text) => Message::text(text),
+                    Outgoing::Binary(bytes) => Message::binary(bytes),
+                    Outgoing::Ping(payload) => Message::Ping(payload.into()),
+                    Outgoing::Close(code, reason) => Message::Close(Some(CloseFrame {
+                        code: code.into(),
+                        reason: reason.into(),
+                    })),
+                };
+                match &message {
+                    Message::Close(frame) => socket.close(frame.clone())?,
+                    _ => socket.send(message.clone())?,
+                }
+                events.send(WsEvent::Sent(message))?;
+            }
+            Err(TryRecvError::Empty) => {}
+            // The app dropped the connection.
+            Err(TryRecvError::Disconnected) => {
+                let _ = socket.close(None);
+                return Ok(());
+            }
+        }
+
+        match socket.read() {
+            Ok(message) => events.send(WsEvent::Received(message))?,
+            Err(tungstenite::Error::Io(e))
+                if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) =>
+            {
+                // Replies to pings and closes are queued by `read` and written here.
+                match socket.flush() {
+                    Err(tungstenite::Error::Io(e))
+                        if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+                    result => result?,
+                }
+            }
+            Err(tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed) => {
+                events.send(WsEvent::Closed)?;
+                return Ok(());
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}